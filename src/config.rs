@@ -0,0 +1,158 @@
+/********************************** Data Directory ***************************************/
+
+use std::{env, fs, path::{Path, PathBuf}, sync::OnceLock};
+use crate::types::{CEerror, CEResult};
+
+// Bump when the files below change in an incompatible way, old layouts stay untouched
+const LAYOUT_VERSION: &str = "v1";
+const APP_DIR: &str = "mtg-analyzer";
+pub const DATA_DIR_ENV: &str = "MTG_ANALYZER_DATA";
+pub const SCRYFALL_ENV: &str = "MTG_ANALYZER_SCRYFALL";
+pub const COMBOS_ENV: &str = "MTG_ANALYZER_COMBOS";
+pub const RULES_ENV: &str = "MTG_ANALYZER_RULES";
+const SCRYFALL_URL: &str = "https://api.scryfall.com";
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComboSource {
+    // The community google sheet, downloaded once a week
+    Sheet,
+    // A Commander Spellbook json export on disk
+    Spellbook(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub data_dir: PathBuf,
+    pub scryfall_url: String,
+    // Card json is read from here instead of scryfall, for reproducible runs without network
+    pub fixtures: Option<PathBuf>,
+    pub combo_source: ComboSource,
+    // Classification rules on top of the built in ones, rules.json in the data directory when not set
+    pub rules: Option<PathBuf>,
+}
+
+impl Config {
+    // --data-dir wins over the environment variable, which wins over the XDG default
+    pub fn new(data_dir: Option<&str>) -> Config {
+        let root = match data_dir {
+            Some(t) => PathBuf::from(t),
+            None => match env::var(DATA_DIR_ENV) {
+                Ok(t) if !t.is_empty() => PathBuf::from(t),
+                _ => default_dir(),
+            },
+        };
+
+        let scryfall_url = match env::var(SCRYFALL_ENV) {
+            Ok(t) if !t.is_empty() => t,
+            _ => SCRYFALL_URL.to_string(),
+        };
+
+        let combo_source = match env::var(COMBOS_ENV) {
+            Ok(t) if !t.is_empty() => ComboSource::Spellbook(PathBuf::from(t)),
+            _ => ComboSource::Sheet,
+        };
+
+        let rules = match env::var(RULES_ENV) {
+            Ok(t) if !t.is_empty() => Some(PathBuf::from(t)),
+            _ => None,
+        };
+
+        Config { data_dir: root.join(LAYOUT_VERSION), scryfall_url, fixtures: None, combo_source, rules }
+    }
+    pub fn rules(mut self, file: Option<&str>) -> Config {
+        if let Some(t) = file {
+            self.rules = Some(PathBuf::from(t));
+        }
+        self
+    }
+    pub fn combos(mut self, file: Option<&str>) -> Config {
+        if let Some(t) = file {
+            self.combo_source = ComboSource::Spellbook(PathBuf::from(t));
+        }
+        self
+    }
+    pub fn scryfall(mut self, url: Option<&str>) -> Config {
+        if let Some(t) = url {
+            self.scryfall_url = t.to_string();
+        }
+        self
+    }
+    pub fn fixtures(mut self, dir: Option<&str>) -> Config {
+        if let Some(t) = dir {
+            self.fixtures = Some(PathBuf::from(t));
+        }
+        self
+    }
+    pub fn database(&self) -> PathBuf {
+        self.data_dir.join("database.txt")
+    }
+    pub fn index(&self) -> PathBuf {
+        self.data_dir.join("index.txt")
+    }
+    // Each source keeps its own file, switching sources never reads the other one's data
+    pub fn combo(&self) -> PathBuf {
+        match self.combo_source {
+            ComboSource::Sheet => self.data_dir.join("combo_sheet.txt"),
+            ComboSource::Spellbook(_) => self.data_dir.join("combo_spellbook.txt"),
+        }
+    }
+    pub fn saves(&self) -> PathBuf {
+        self.data_dir.join("save")
+    }
+    pub fn decks(&self) -> PathBuf {
+        self.data_dir.join("decks")
+    }
+    // An explicitly given rules file has to exist, the one in the data directory is optional
+    pub fn rules_file(&self) -> Option<PathBuf> {
+        match &self.rules {
+            Some(t) => Some(t.clone()),
+            None => Some(self.data_dir.join("rules.json")).filter(|t| t.is_file()),
+        }
+    }
+    // A decklist is either a path to a file or the name of a file in decks/
+    pub fn decklist(&self, input: &str) -> PathBuf {
+        let path = Path::new(input);
+
+        if path.is_file() {
+            return path.to_path_buf();
+        }
+        self.decks().join(input)
+    }
+    fn create(&self) -> CEResult<()> {
+        for dir in [self.data_dir.clone(), self.saves(), self.decks()] {
+            if let Err(e) = fs::create_dir_all(&dir) {
+                return Err(CEerror::ConfigError(format!("Can not create {}: {}", dir.display(), e)));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Sets up the data directory once at startup, later calls keep the first configuration
+pub fn init(config: Config) -> CEResult<&'static Config> {
+    let config = CONFIG.get_or_init(|| config);
+    config.create()?;
+    Ok(config)
+}
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(|| Config::new(None))
+}
+// Name a deck is saved under, independent of where its decklist was read from
+pub fn deck_name(input: &str) -> String {
+    match Path::new(input).file_name() {
+        Some(t) => t.to_string_lossy().to_string(),
+        None => input.to_string(),
+    }
+}
+
+fn default_dir() -> PathBuf {
+    match env::var("XDG_DATA_HOME") {
+        Ok(t) if !t.is_empty() => PathBuf::from(t).join(APP_DIR),
+        _ => match env::var("HOME") {
+            Ok(t) if !t.is_empty() => PathBuf::from(t).join(".local").join("share").join(APP_DIR),
+            _ => PathBuf::from(".").join(APP_DIR),
+        },
+    }
+}
@@ -5,38 +5,135 @@ pub mod user_import {
 
     use crate::types::{CEerror, CEResult};
 
-    pub fn decklist(filename: String) -> CEResult<Vec<String>>{
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Section {
+        Commander,
+        Deck,
+        Sideboard,
+        Companion,
+        // MTGO sideboard next to annotated commanders, only a card with the companion keyword is the companion
+        MaybeCompanion,
+        About,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct DeckEntry {
+        pub quantity: u8,
+        pub name: String,
+        pub section: Section,
+    }
+
+    pub fn decklist(filename: String) -> CEResult<Vec<DeckEntry>>{
     println!("Load decklist {}", filename);
 
-    let path = format!("decks/{}", filename);
+    let path = crate::config::get().decklist(&filename);
 
   match fs::read_to_string(path){
-        Ok(t) => return Ok( t.replace("\r", "")
-            .split("\n")
-            .flat_map(str::parse::<String>)
-            .collect::<Vec<String>>() ),
+        Ok(t) => {
+            if filename.to_lowercase().ends_with(".dek") {
+                return super::mtgo::parse_dek(&t);
+            }
+            return parse_decklist(&t);
+        },
         Err(_) => return Err(CEerror::FailImportDeck(String::from("Can not read from path"))),
     }; 
 }
-    pub fn quantity_card(decklist: &String) -> CEResult<Vec<String>> {
-    let mut qty_card  = decklist.splitn(2, " ");
-    let mut result = Vec::new();
-
-    match qty_card.next() {
-        Some(t) => {
-            result.push(t.to_string());
-            match qty_card.next() {
-                Some(t) => {
-                    result.push(t.to_string());
-                    return Ok(result);
-                },
-                None => return Err(CEerror::FailImportDeck(String::from("Error getting Cardname. Empty string"))),
+    // Understands plain "N Name" lists as well as Arena and MTGO exports with section headers
+    pub fn parse_decklist(contents: &str) -> CEResult<Vec<DeckEntry>> {
+        let mut section = Section::Deck;
+        let mut result = Vec::<DeckEntry>::new();
+
+        for line in contents.replace("\r", "").split('\n') {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with("//") || line.starts_with('#') {
+                continue;
+            }
+            if let Some(t) = header(line) {
+                section = t;
+                continue;
+            }
+            // Arena puts the deck name and similar metadata in front of the list
+            if section == Section::About {
+                continue;
             }
-        },
-        None => return Err(CEerror::FailImportDeck(String::from("Error getting Quantity. Empty string"))),
 
+            result.push(entry(line, section)?);
+        }
+        Ok(result)
+    }
+    fn header(line: &str) -> Option<Section> {
+        match line.trim_end_matches(':').to_lowercase().as_str() {
+            "commander" => Some(Section::Commander),
+            "deck" | "main" | "mainboard" => Some(Section::Deck),
+            "sideboard" | "maybeboard" => Some(Section::Sideboard),
+            "companion" => Some(Section::Companion),
+            "about" => Some(Section::About),
+            _ => None,
+        }
+    }
+    // Parses "1 Sol Ring", "1x Sol Ring" and "1 Sol Ring (CMR) 472", *CMDR* still marks the commander in plain lists
+    pub fn entry(line: &str, section: Section) -> CEResult<DeckEntry> {
+        let mut qty_card = line.trim().splitn(2, ' ');
+        let mut section = section;
+
+        let quantity = match qty_card.next() {
+            Some(t) => match t.trim_end_matches(['x', 'X']).parse::<u8>() {
+                Ok(t) => t,
+                Err(_) => return Err(CEerror::FailImportDeck(format!("Error getting Quantity of \"{}\"", line))),
+            },
+            None => return Err(CEerror::FailImportDeck(String::from("Error getting Quantity. Empty string"))),
+        };
+        let mut name = match qty_card.next() {
+            Some(t) => t.trim().to_string(),
+            None => return Err(CEerror::FailImportDeck(String::from("Error getting Cardname. Empty string"))),
+        };
+
+        if name.contains("*CMDR*") {
+            section = Section::Commander;
+            name = name.replace("*CMDR*", "");
+        }
+        // Foil and etched markers of moxfield exports
+        name = name.replace("*F*", "").replace("*E*", "").trim().to_string();
+        name = strip_printing(&name);
+
+        // Split cards are named "Fire // Ice" in the card data
+        if name.contains('/') {
+            name = name.split('/')
+                .map(str::trim)
+                .filter(|half| !half.is_empty())
+                .collect::<Vec<&str>>()
+                .join(" // ");
+        }
+        if name.is_empty() {
+            return Err(CEerror::FailImportDeck(String::from("Error getting Cardname. Empty string")));
+        }
+
+        Ok(DeckEntry { quantity, name, section })
+    }
+    // Cuts " (CMR) 472" or " (CMR)" from the end of a card name
+    pub(super) fn strip_printing(name: &str) -> String {
+        match name.rfind(" (") {
+            Some(i) => {
+                let rest = &name[i + 2..];
+                match rest.find(')') {
+                    Some(j) => {
+                        let set = &rest[..j];
+                        let number = rest[j + 1..].trim();
+                        if !set.is_empty() 
+                        && set.len() <= 6 
+                        && set.chars().all(|c| c.is_ascii_alphanumeric())
+                        && !number.contains(' ') {
+                            return name[..i].trim().to_string();
+                        }
+                        name.to_string()
+                    },
+                    None => name.to_string(),
+                }
+            },
+            None => name.to_string(),
+        }
     }
-}
 
 
 // Needed if user interaction is required
@@ -50,59 +147,414 @@ pub mod user_import {
     input_vec
 }
 }
+/*********************************** MTGO .dek ********************************************/
+pub mod mtgo {
+    use quick_xml::{Reader, events::Event};
+
+    use crate::types::{CEerror, CEResult, Card, Deck};
+    use super::user_import::{DeckEntry, Section};
+
+    /* MTGO keeps the commander in the sideboard. The annotation MTGO itself writes for commanders could not be
+       confirmed, so the import treats any non-zero annotation as commander and falls back to the sideboard size.
+       Exports only mark commanders apart from the companion for our own import */
+    const COMMANDER_ANNOTATION: &str = "1";
+
+    pub fn parse_dek(contents: &str) -> CEResult<Vec<DeckEntry>> {
+        let mut reader = Reader::from_str(contents);
+        reader.trim_text(true);
+
+        let mut buf = Vec::new();
+        let mut main = Vec::<DeckEntry>::new();
+        let mut sideboard = Vec::<(DeckEntry, bool)>::new();
+
+        loop {
+            match reader.read_event(&mut buf) {
+                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) if e.name() == b"Cards" => {
+                    let mut quantity = None;
+                    let mut name = None;
+                    let mut in_sideboard = false;
+                    let mut annotated = false;
+
+                    for attribute in e.attributes() {
+                        let attribute = match attribute {
+                            Ok(t) => t,
+                            Err(_) => return Err(CEerror::FailImportDeck(String::from("Broken attribute in .dek file"))),
+                        };
+                        let value = match attribute.unescape_and_decode_value(&reader) {
+                            Ok(t) => t,
+                            Err(_) => return Err(CEerror::FailImportDeck(String::from("Broken attribute in .dek file"))),
+                        };
+                        match attribute.key {
+                            b"Quantity" => quantity = value.parse::<u8>().ok(),
+                            b"Name" => name = Some(value),
+                            b"Sideboard" => in_sideboard = value.to_lowercase() == "true",
+                            b"Annotation" => annotated = value != "0" && !value.is_empty(),
+                            _ => (),
+                        }
+                    }
+
+                    let entry = match (quantity, name) {
+                        (Some(quantity), Some(name)) => DeckEntry { quantity, name, section: Section::Deck },
+                        _ => return Err(CEerror::FailImportDeck(String::from("Card without Name or Quantity in .dek file"))),
+                    };
+
+                    if in_sideboard {
+                        sideboard.push((entry, annotated));
+                    } else {
+                        main.push(entry);
+                    }
+                },
+                Ok(Event::Eof) => break,
+                Err(e) => return Err(CEerror::FailImportDeck(format!("Can not read .dek file: {}", e))),
+                _ => (),
+            }
+            buf.clear();
+        }
+
+        // Without annotation a sideboard of one or two cards in total is the command zone, everything else stays sideboard.
+        // With annotated commanders the other sideboard cards may hold the companion.
+        let annotated = sideboard.iter().any(|(_, annotated)| *annotated);
+        let command_zone = (1..=2).contains(&sideboard.iter().map(|(entry, _)| entry.quantity as usize).sum::<usize>())
+            && main.iter().map(|e| e.quantity as usize).sum::<usize>() < 100;
+
+        for (mut entry, commander) in sideboard {
+            entry.section = if annotated {
+                if commander { Section::Commander } else { Section::MaybeCompanion }
+            } else if command_zone {
+                Section::Commander
+            } else {
+                Section::Sideboard
+            };
+            main.push(entry);
+        }
+
+        Ok(main)
+    }
+    pub fn to_dek(deck: &Deck) -> String {
+        let mut dek = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        dek.push_str("<Deck xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n");
+        dek.push_str("  <NetDeckID>0</NetDeckID>\n");
+        dek.push_str("  <PreconstructedDeckID>0</PreconstructedDeckID>\n");
+
+        for (name, quantity) in count(&deck.library) {
+            dek.push_str(&line(&name, quantity, false, "0"));
+        }
+        for (name, quantity) in count(&deck.commander) {
+            dek.push_str(&line(&name, quantity, true, COMMANDER_ANNOTATION));
+        }
+        for (name, quantity) in count(&deck.companion) {
+            dek.push_str(&line(&name, quantity, true, "0"));
+        }
+
+        dek.push_str("</Deck>\n");
+        dek
+    }
+    // Library holds one Card per copy, the .dek format one line per name
+    fn count(cards: &[Card]) -> Vec<(String, usize)> {
+        let mut result = Vec::<(String, usize)>::new();
+
+        for card in cards {
+            match result.iter_mut().find(|(name, _)| *name == card.name) {
+                Some((_, quantity)) => *quantity += 1,
+                None => result.push((card.name.to_string(), 1)),
+            }
+        }
+        result
+    }
+    fn line(name: &str, quantity: usize, sideboard: bool, annotation: &str) -> String {
+        format!("  <Cards Quantity=\"{}\" Sideboard=\"{}\" Name=\"{}\" Annotation=\"{}\" />\n", quantity, sideboard, escape(name), annotation)
+    }
+    fn escape(input: &str) -> String {
+        input.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+            .replace('\'', "&apos;")
+    }
+}
 /********************************* Scryfall Import ****************************************/
 pub mod scryfall {
-    use reqwest::blocking;
-    use serde_json::Value;
+    use std::{collections::HashMap, fs, path::PathBuf, sync::{Mutex, OnceLock}, thread, time::{Duration, Instant}};
+    use reqwest::{blocking, StatusCode};
+    use serde_json::{json, Value};
     use crate::types::{CEerror, CEResult};
     use crate::logic::database;
+    use crate::config;
+
+    // Scryfall asks for 50-100 ms between requests and at most 75 identifiers per collection request
+    const REQUEST_DELAY: Duration = Duration::from_millis(100);
+    const RETRIES: u32 = 4;
+    const BACKOFF: Duration = Duration::from_millis(500);
+    pub const COLLECTION_SIZE: usize = 75;
+
+    static LAST_REQUEST: Mutex<Option<Instant>> = Mutex::new(None);
+    // One connection pool for every request of the run
+    static CLIENT: OnceLock<blocking::Client> = OnceLock::new();
+
+    // Everything scryfall is asked for goes through here, paths are relative to the api root
+    pub trait Fetch {
+        fn fetch(&self, path: &str) -> CEResult<String>;
+        fn post(&self, path: &str, body: &Value) -> CEResult<String>;
+    }
+
+    pub struct HttpFetch {
+        pub base: String,
+    }
+    impl HttpFetch {
+        fn url(&self, path: &str) -> String {
+            // Bulk download links are absolute
+            if path.starts_with("http") {
+                path.to_string()
+            } else {
+                format!("{}{}", self.base.trim_end_matches('/'), path)
+            }
+        }
+        // Waits for the rate limit and retries with doubling backoff on network errors, 429 and 5xx.
+        // Other answers, 404 included, are returned as they carry scryfalls error object
+        fn send(&self, request: impl Fn() -> reqwest::Result<blocking::Response>) -> CEResult<String> {
+            let mut backoff = BACKOFF;
+            let mut reason = String::new();
+
+            for attempt in 0..RETRIES {
+                if attempt > 0 {
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                }
+                throttle();
 
+                match request() {
+                    Ok(t) => {
+                        let status = t.status();
+                        if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+                            reason = format!("scryfall answered {}", status);
+                            continue;
+                        }
+                        return t.text().map_err(|e| CEerror::ScryfallError(e.to_string()));
+                    },
+                    Err(e) => reason = e.to_string(),
+                }
+            }
+            Err(CEerror::ScryfallError(format!("gave up after {} attempts: {}", RETRIES, reason)))
+        }
+    }
+    impl Fetch for HttpFetch {
+        fn fetch(&self, path: &str) -> CEResult<String> {
+            let client = client()?;
+            let url = self.url(path);
+
+            self.send(|| client.get(&url).send())
+        }
+        fn post(&self, path: &str, body: &Value) -> CEResult<String> {
+            let client = client()?;
+            let url = self.url(path);
+
+            self.send(|| client.post(&url).json(body).send())
+        }
+    }
+
+    // Stand-in for the api: cards as <file name>.json, bulk-data.json and the bulk file named like its download link
+    pub struct FixtureFetch {
+        pub dir: PathBuf,
+    }
+    impl FixtureFetch {
+        fn card(&self, name: &str) -> Option<String> {
+            fs::read_to_string(self.dir.join(format!("{}.json", FixtureFetch::file_name(name)))).ok()
+        }
+        /* Normalized name with words joined by dashes, punctuation dropped. "Fire // Ice" is fire-ice.json
+           instead of a path into a directory, "Thassa's Oracle" is thassas-oracle.json */
+        pub fn file_name(name: &str) -> String {
+            let mut result = String::new();
+
+            for c in database::normalize(name).chars() {
+                if c.is_alphanumeric() {
+                    result.push(c);
+                } else if (c.is_whitespace() || c == '/' || c == '-') && !result.is_empty() && !result.ends_with('-') {
+                    result.push('-');
+                }
+            }
+            result.trim_end_matches('-').to_string()
+        }
+    }
+    impl Fetch for FixtureFetch {
+        fn fetch(&self, path: &str) -> CEResult<String> {
+            if let Some(name) = path.split("?exact=").nth(1).or_else(|| path.split("?fuzzy=").nth(1)) {
+                // Same answer scryfall gives for unknown cards
+                return Ok(self.card(&decode(name)).unwrap_or_else(|| String::from("{\"object\": \"error\", \"code\": \"not_found\"}")));
+            }
+
+            let file = match path.rsplit('/').next() {
+                Some(t) if !t.is_empty() && t.contains('.') => t.to_string(),
+                Some(t) if !t.is_empty() => format!("{}.json", t),
+                _ => return Err(CEerror::APIError),
+            };
+            fs::read_to_string(self.dir.join(&file)).map_err(|e| CEerror::ScryfallError(format!("{}: {}", file, e)))
+        }
+        fn post(&self, _path: &str, body: &Value) -> CEResult<String> {
+            let mut data = Vec::<Value>::new();
+            let mut not_found = Vec::<Value>::new();
+
+            for identifier in body["identifiers"].as_array().unwrap_or(&Vec::new()) {
+                match identifier["name"].as_str().and_then(|name| self.card(name)) {
+                    Some(t) => data.push(serde_json::from_str(&t).map_err(|e| CEerror::ScryfallError(e.to_string()))?),
+                    None => not_found.push(identifier.clone()),
+                }
+            }
+            Ok(json!({"object": "list", "not_found": not_found, "data": data}).to_string())
+        }
+    }
+
+    // A name scryfall could not turn into a card and what went wrong
+    #[derive(Debug, Clone)]
+    pub struct Unresolved {
+        pub name: String,
+        pub reason: String,
+    }
+    #[derive(Debug, Default)]
+    pub struct Collection {
+        // Requested name to the card scryfall answered with
+        pub cards: HashMap<String, Value>,
+        pub unresolved: Vec<Unresolved>,
+    }
+
+    pub fn fetcher() -> Box<dyn Fetch> {
+        let config = config::get();
+
+        match &config.fixtures {
+            Some(dir) => Box::new(FixtureFetch { dir: dir.clone() }),
+            None => Box::new(HttpFetch { base: config.scryfall_url.to_string() }),
+        }
+    }
     pub fn get(cardname: &String) -> CEResult<String> {
-        let request = match exact_request(cardname) {
+        get_from(&*fetcher(), cardname)
+    }
+    pub fn get_from(fetch: &dyn Fetch, cardname: &String) -> CEResult<String> {
+        let request = match exact_request(fetch, cardname) {
             Ok(t) => {
-                let v: Value = serde_json::from_str(&t).expect("No string found in request_card");
+                let v: Value = serde_json::from_str(&t).map_err(|_| CEerror::APIError)?;
                 if v["code"] == "not_found".to_string() {
                     println!("Card not found by name, try fuzzy request");
-                    match fuzzy_request(cardname) {
+                    match fuzzy_request(fetch, cardname) {
                         Ok(t) => return Ok(t),
                         Err(e) => return Err(e),
                     };
                 }
                 Ok(t)
             },
-            Err(_) => match fuzzy_request(cardname) {
+            Err(_) => match fuzzy_request(fetch, cardname) {
                 Ok(t) => Ok(t),
                 Err(_) => Err(CEerror::CardNotFound),
             }
         };
         request
     }
-    fn fuzzy_request(cardname: &String) -> CEResult<String> {
-        let mut api = String::from("https://api.scryfall.com/cards/named?fuzzy=");
-        api = format!("{}{}", api, make_fuzzy(cardname));
-        let request = match
-            blocking::get(api) {
-                Ok(t) => match t.text() {
-                    Ok(t) => Ok(t),
-                    Err(_) => Err(CEerror::APIError),
+    fn fuzzy_request(fetch: &dyn Fetch, cardname: &String) -> CEResult<String> {
+        fetch.fetch(&format!("/cards/named?fuzzy={}", make_fuzzy(cardname)))
+    }
+    fn exact_request(fetch: &dyn Fetch, cardname: &str) -> CEResult<String> {
+        fetch.fetch(&format!("/cards/named?exact={}", encode(cardname)))
+    }
+    // Percent encoding for names in a query string, "Fire // Ice" and "Lim-Dûl's Vault" arrive as written
+    fn encode(input: &str) -> String {
+        let mut result = String::new();
+
+        for byte in input.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => result.push(byte as char),
+                _ => result.push_str(&format!("%{:02X}", byte)),
+            }
+        }
+        result
+    }
+    // Reverse of encode, + is a space like in fuzzy requests
+    fn decode(input: &str) -> String {
+        let mut bytes = Vec::<u8>::new();
+        let mut rest = input.as_bytes();
+
+        while let Some((&byte, tail)) = rest.split_first() {
+            rest = tail;
+            match byte {
+                b'+' => bytes.push(b' '),
+                b'%' if rest.len() >= 2 => match u8::from_str_radix(&String::from_utf8_lossy(&rest[..2]), 16) {
+                    Ok(t) => {
+                        bytes.push(t);
+                        rest = &rest[2..];
+                    },
+                    Err(_) => bytes.push(byte),
                 },
-                Err(_) => Err(CEerror::APIError),
-        };
-        request
+                _ => bytes.push(byte),
+            }
+        }
+        String::from_utf8_lossy(&bytes).to_string()
     }
-    fn exact_request(cardname: &String) -> CEResult<String> {
-        let mut api = String::from("https://api.scryfall.com/cards/named?exact=");
-        api = format!("{}{}", api, *cardname);
-    
-        let request = match blocking::get(api) {
-                Ok(t) => match t.text() {
-                    Ok(t) => Ok(t),
-    
-                    Err(_) => Err(CEerror::APIError),
+    // Resolves names in batches through /cards/collection, names the batch did not know get a fuzzy request each
+    pub fn collection(names: &[String]) -> Collection {
+        collection_from(&*fetcher(), names)
+    }
+    pub fn collection_from(fetch: &dyn Fetch, names: &[String]) -> Collection {
+        let mut result = Collection::default();
+
+        for batch in names.chunks(COLLECTION_SIZE) {
+            let identifiers: Vec<Value> = batch.iter().map(|name| json!({"name": name})).collect();
+            let mut retry = Vec::<&String>::new();
+
+            match fetch.post("/cards/collection", &json!({"identifiers": identifiers})) {
+                Ok(t) => match serde_json::from_str::<Value>(&t) {
+                    Ok(v) if v["object"] == "error" => {
+                        let reason = v["details"].as_str().unwrap_or("collection request refused").to_string();
+                        result.unresolved.extend(batch.iter().map(|name| Unresolved { name: name.to_string(), reason: reason.to_string() }));
+                    },
+                    Ok(v) => {
+                        // Answers are not in request order and split cards come back under their full name
+                        let found = database::index(&v["data"]);
+                        for name in batch {
+                            match database::get(name, &found) {
+                                Ok(card) => { result.cards.insert(name.to_string(), card); },
+                                Err(_) => retry.push(name),
+                            }
+                        }
+                    },
+                    Err(e) => result.unresolved.extend(batch.iter().map(|name| Unresolved { name: name.to_string(), reason: format!("invalid answer: {}", e) })),
                 },
-                Err(_) => Err(CEerror::APIError),
-            };
-        request
+                Err(e) => result.unresolved.extend(batch.iter().map(|name| Unresolved { name: name.to_string(), reason: e.to_string() })),
+            }
+
+            for name in retry {
+                match fuzzy_request(fetch, name) {
+                    Ok(t) => match serde_json::from_str::<Value>(&t) {
+                        Ok(v) if v["object"] == "card" => { result.cards.insert(name.to_string(), v); },
+                        Ok(v) => result.unresolved.push(Unresolved {
+                            name: name.to_string(),
+                            reason: v["details"].as_str().unwrap_or("no card with this name").to_string(),
+                        }),
+                        Err(e) => result.unresolved.push(Unresolved { name: name.to_string(), reason: format!("invalid answer: {}", e) }),
+                    },
+                    Err(e) => result.unresolved.push(Unresolved { name: name.to_string(), reason: e.to_string() }),
+                }
+            }
+        }
+        result
+    }
+    pub(super) fn client() -> CEResult<&'static blocking::Client> {
+        if let Some(client) = CLIENT.get() {
+            return Ok(client);
+        }
+        let client = blocking::Client::builder()
+            .user_agent(concat!("mtg-analyzer/", env!("CARGO_PKG_VERSION")))
+            .timeout(Duration::from_secs(300))
+            .build()
+            .map_err(|e| CEerror::ScryfallError(e.to_string()))?;
+        Ok(CLIENT.get_or_init(|| client))
+    }
+    fn throttle() {
+        let mut last = LAST_REQUEST.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(t) = *last {
+            let elapsed = t.elapsed();
+            if elapsed < REQUEST_DELAY {
+                thread::sleep(REQUEST_DELAY - elapsed);
+            }
+        }
+        *last = Some(Instant::now());
     }
     fn make_fuzzy(cardname: &String ) -> String {
 
@@ -129,7 +581,8 @@ pub mod scryfall {
                // 3 => word = word[..2].to_string(),
                 3 => (),
                 4 => (),
-                _ => word = word[..(length-( length/2 ) ) + 1 ].to_string(),
+                // Counted in chars, names like Andúril have multibyte letters
+                _ => word = buffer.iter().take((length-( length/2 ) ) + 1).collect(),
             };
     
             if i == 0 {
@@ -140,40 +593,26 @@ pub mod scryfall {
             i += 1;
         }
     
-        fuzzy_string.replace(" ", "+")
+        fuzzy_string.split(' ').map(encode).collect::<Vec<String>>().join("+")
     }
     pub fn get_bulk() -> CEResult<()> {
-        let mut api = String::from("https://api.scryfall.com/bulk-data");
+        let fetch = fetcher();
 
         println!("Downloading database from scryfall....");
 
-        match blocking::get(api) {
-            Ok(t) => match t.text() {
-                Ok(t) => {
-                    let v: Value = serde_json::from_str(&t).expect("Bulk-data frame was not retrieved");
-                    if v["code"] == "not_found".to_string() {
-                        println!("Bulk-Data temporally not available, due to not recieving data frame");
-                    }
-                    api = v["data"][0]["download_uri"].to_string().replace("\"", "");
-                    
-                    match blocking::get(api) {
-                        Ok(t) => match t.text() {
-                            Ok(t) => {
-                                let v: Value = serde_json::from_str(&t).expect("Bulk-Data can not be formated to json");
-                                if v["code"] == "not_found".to_string() {
-                                    println!("Bulk-Data temporally not available");
-                                }
-                                return database::save(&t);
-                            },
-                            Err(_) => return Err(CEerror::APIError), 
-                        }
-                        Err(_) => return Err(CEerror::APIError), 
-                    }
-                },
-                Err(_) => return Err(CEerror::APIError),
-            }
-            Err(_) => return Err(CEerror::APIError),
+        let frame = fetch.fetch("/bulk-data")?;
+        let v: Value = serde_json::from_str(&frame).map_err(|_| CEerror::APIError)?;
+        if v["code"] == "not_found".to_string() {
+            println!("Bulk-Data temporally not available, due to not recieving data frame");
+        }
+        let api = v["data"][0]["download_uri"].to_string().replace("\"", "");
+
+        let t = fetch.fetch(&api)?;
+        let v: Value = serde_json::from_str(&t).map_err(|_| CEerror::APIError)?;
+        if v["code"] == "not_found".to_string() {
+            println!("Bulk-Data temporally not available");
         }
+        database::save(&v)
     }
 }
 /********************************** Combo Import ******************************************/
@@ -182,87 +621,150 @@ pub mod scryfall {
 
 pub mod combo {
     use reqwest::blocking;
-    use crate::{types::{CEResult, CEerror, Deck}};
+    use crate::{types::{CEResult, CEerror, Colors, Deck}, config::{self, ComboSource}};
+    use serde::{Serialize, Deserialize};
     use serde_json::Value;
-    use std::{fs::{self, *}, io::{prelude::*, BufReader}, time::{SystemTime, Duration}, ops::Add};
+    use std::{fs::{self, *}, io::{prelude::*, BufReader}, path::Path, time::{SystemTime, Duration}, ops::Add};
    
+    // Columns of the combo sheet, cards take the ten slots after the id
+    const ID: usize = 0;
+    const PIECES: std::ops::RangeInclusive<usize> = 1..=10;
+    const COLOR_IDENTITY: usize = 11;
+    const PREREQUISITES: usize = 12;
+    const STEPS: usize = 13;
+    const RESULTS: usize = 14;
+    // Combos missing more pieces than this are not worth suggesting
+    pub const MAX_MISSING: usize = 2;
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Combo {
+        pub id: String,
+        pub pieces: Vec<String>,
+        pub color_identity: Vec<Colors>,
+        pub prerequisites: Vec<String>,
+        pub steps: Vec<String>,
+        // What the combo produces, e.g. "Infinite mana" or "Win the game"
+        pub results: Vec<String>,
+    }
+
+    impl Combo {
+        pub fn from_row(row: &[String]) -> Option<Combo> {
+            let pieces: Vec<String> = PIECES.filter_map(|i| row.get(i))
+                .map(|piece| piece.trim().to_string())
+                .take_while(|piece| !piece.is_empty())
+                .collect();
+
+            if pieces.is_empty() {
+                return None;
+            }
+
+            let color_identity = row.get(COLOR_IDENTITY).map(String::as_str).unwrap_or("")
+                .split(|c: char| !c.is_alphabetic())
+                .filter(|symbol| symbol.len() == 1)
+                .filter_map(|symbol| symbol.chars().next().and_then(Colors::from_symbol))
+                .collect();
+
+            Some(Combo {
+                id: row.get(ID).map(|id| id.trim().to_string()).unwrap_or_default(),
+                pieces,
+                color_identity,
+                prerequisites: lines(row.get(PREREQUISITES)),
+                steps: lines(row.get(STEPS)),
+                results: lines(row.get(RESULTS)),
+            })
+        }
+    }
+
     #[derive(Debug)]
     pub struct ComboResult {
         commander_combo_piece: bool,
-        pub combo: Vec<String>,
-        pub num_pieces: usize,
+        pub combo: Combo,
+        // Pieces neither in library nor command zone, empty for completed combos
+        pub missing: Vec<String>,
     }
 
     impl ComboResult {
-        pub fn new() -> ComboResult {
-            ComboResult {
-                commander_combo_piece: false,
-                combo: Vec::new(),
-                num_pieces: 0,
-            }
-        }
-        pub fn from(commander_combo_piece: bool, combo: Vec<String>, num_pieces: usize) -> ComboResult {
+        pub fn from(commander_combo_piece: bool, combo: Combo, missing: Vec<String>) -> ComboResult {
             ComboResult { 
                 commander_combo_piece: commander_combo_piece, 
                 combo: combo, 
-                num_pieces: num_pieces }
+                missing: missing }
+        }
+        // At least one piece is a commander and always available
+        pub fn commander_combo_piece(&self) -> bool {
+            self.commander_combo_piece
+        }
+        pub fn pieces(&self) -> &[String] {
+            &self.combo.pieces
+        }
+        pub fn num_pieces(&self) -> usize {
+            self.combo.pieces.len()
+        }
+        pub fn is_complete(&self) -> bool {
+            self.missing.is_empty()
         }
     }
 
+    // Completed combos first, then near misses with the most pieces already in the deck
     pub fn search (deck: &Deck) -> CEResult<Vec<ComboResult>> { 
         
         let database = load()?;
         let mut results: Vec<ComboResult> = Vec::new(); 
-        let empty = String::from("");
-
-        for combo in database { 
-   
-    
-            // Unused combo slots in data frame are empty, therefore a maximum of 10 slots minus empty ones is num_pieces
-            let mut unused = 0;
+        let identity = deck.color_identity();
 
-            for elements in &combo {
-                if *elements == empty { unused += 1 }
-            }
-
-            let num_pieces = 10 - unused;
+        for row in database { 
+            let combo = match Combo::from_row(&row) {
+                Some(t) => t,
+                None => continue,
+            };
 
-            let mut hit: usize = 0;
+            let mut missing = Vec::<String>::new();
             let mut commander_combo_piece: bool = false; 
-            // starts at 1 because 0 is number of combo initiated by source
-            for i in 1..=num_pieces {    
-                // looking through deck to fetch all available combos, take only combos which are completed therefore hit == num_pieces
-                for card in &deck.library {
-                    if &card.name == &combo[i] {
-                        hit += 1;
-                    } 
+
+            for piece in &combo.pieces {    
+                if deck.commander.iter().any(|commander| &commander.name == piece) {
+                    commander_combo_piece = true;
+                } else if !deck.library.iter().any(|card| &card.name == piece) {
+                    missing.push(piece.to_string());
                 }
-                for commander in &deck.commander {
-                    if commander.name == combo[i] {
-                        hit += 1;
-                        commander_combo_piece = true;
-                    }
-                }  
             }
-        
-            if hit == num_pieces { 
-                results.push(ComboResult::from(commander_combo_piece, combo, num_pieces))
-                }
+
+            if missing.len() > MAX_MISSING || missing.len() == combo.pieces.len() {
+                continue;
+            }
+            // Suggestions have to be playable with the commander
+            if !missing.is_empty() && !combo.color_identity.iter().all(|color| identity.contains(color)) {
+                continue;
+            }
+
+            results.push(ComboResult::from(commander_combo_piece, combo, missing));
         }
 
+        results.sort_by(|a, b| a.missing.len().cmp(&b.missing.len())
+            .then((b.num_pieces() - b.missing.len()).cmp(&(a.num_pieces() - a.missing.len()))));
+
         Ok(results) 
     } 
+    fn lines(raw: Option<&String>) -> Vec<String> {
+        match raw {
+            Some(t) => t.lines()
+                .map(|line| line.trim().to_string())
+                .filter(|line| !line.is_empty())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
     fn load() -> CEResult<Vec<Vec<String>>> {
         let mut contents = String::new();
 
         println!("Open combo data from system");
 
-        match File::open("combo.txt") {
+        match File::open(config::get().combo()) {
             Ok(t) => {
                 let mut buf_reader = BufReader::new(t);
-                buf_reader.read_to_string(&mut contents).expect("Can not open combo data");
+                buf_reader.read_to_string(&mut contents).map_err(|_| CEerror::ComboError)?;
 
-                let result: Vec<Vec<String>> = serde_json::from_str(&contents).expect("Json not properly build"); 
+                let result: Vec<Vec<String>> = serde_json::from_str(&contents).map_err(|_| CEerror::ComboError)?; 
 
                 println!("Combo data successfully opened"); 
 
@@ -271,61 +773,151 @@ pub mod combo {
             Err(_) => Err(CEerror::ComboError),
         }
     }
+    // Offline runs can still import an export from disk, only the sheet needs the network
+    pub fn update_local() -> CEResult<()> {
+        match &config::get().combo_source {
+            ComboSource::Spellbook(source) => update_spellbook(&config::get().combo(), source),
+            ComboSource::Sheet => Ok(()),
+        }
+    }
     pub fn update() -> CEResult<()>{
+        let path = config::get().combo();
+
+        if let ComboSource::Spellbook(source) = &config::get().combo_source {
+            return update_spellbook(&path, source);
+        }
 
-        match File::open("combo.txt") {
+        match File::open(&path) {
             Ok(_) => {
-                let metadata = fs::metadata("combo.txt").expect("File found but can not open");
+                let metadata = fs::metadata(&path).expect("File found but can not open");
                 let now = SystemTime::now();
                 
                 if let Ok(time) = metadata.modified() {
-                    // Update every full week
+                    // Update every full week, the old data stays if the download fails
                     if time.add(Duration::from_secs(7*86400)) <= now {
                         println!("File is older than a week: Update....");
-                        match remove_file("combo.txt"){
-                            Ok(_) => println!("Expired combo data removed..."),
-                            Err(_) => println!("Can not remove old combo data..."),
-                        }
-                        match serde_json::to_writer(&File::create("combo.txt").expect("Can not save combos"), &get()?) {
-                            Ok(_)=> return Ok(()),
-                            Err(_) => return Err(CEerror::ComboError),
-                        } 
+                        return store(&path, &get()?);
                     }   
                 }   
                 Ok(())
             },
             Err(_) => {
-                println!("combo.txt not found, create and download data");
-                match serde_json::to_writer(&File::create("combo.txt").expect("Can not save combos"), &get()?) {
-                    Ok(_)=> return Ok(()),
-                    Err(_) => return Err(CEerror::ComboError),
-                }
+                println!("{} not found, create and download data", path.display());
+                store(&path, &get()?)
             },
         }
     }
-    fn get() -> CEResult<Vec<Vec<String>>> { 
-        let mut result = Vec::new();
+    // Imports again whenever the export is newer than the stored combo data
+    fn update_spellbook(path: &Path, source: &Path) -> CEResult<()> {
+        let stored = fs::metadata(path).and_then(|t| t.modified()).ok();
+        let exported = fs::metadata(source).and_then(|t| t.modified()).map_err(|_| CEerror::ComboError)?;
 
-        match request_combo() {
-            Ok(combodata) => {
-                let buffer = seperate_combos(combo_to_json(&combodata)); 
-                
-                for item in buffer {
-                    let elements = item.split("\",\"")
-                                        .flat_map(str::parse::<String>)
-                                        .collect::<Vec<String>>();
-                            // empty slots are length one
-                            if elements.len() != 1 {
-                                result.push(elements);
-                            } 
-                }
+        if let Some(time) = stored {
+            if time >= exported {
+                return Ok(());
+            }
+        }
 
-                return Ok(result);
-            },
-            Err(_) => return Err(CEerror::ComboError),
+        println!("Import combos from {}", source.display());
+        let contents = fs::read_to_string(source).map_err(|_| CEerror::ComboError)?;
+        let sheet = spellbook::rows(&combo_to_json(&contents)?)?;
+
+        if !sheet.skipped.is_empty() {
+            println!("Skipped {} malformed combos", sheet.skipped.len());
+            for error in &sheet.skipped {
+                println!("{}", error);
+            }
         }
+        store(path, &sheet.rows)
+    }
+    fn store(path: &Path, rows: &Vec<Vec<String>>) -> CEResult<()> {
+        match serde_json::to_writer(&File::create(path).map_err(|_| CEerror::ComboError)?, rows) {
+            Ok(_)=> Ok(()),
+            Err(_) => Err(CEerror::ComboError),
+        }
+    }
+    fn get() -> CEResult<Vec<Vec<String>>> { 
+        let json = combo_to_json(&request_combo()?)?;
+        let sheet = rows(&json)?;
 
+        if !sheet.skipped.is_empty() {
+            println!("Skipped {} malformed combo rows", sheet.skipped.len());
+            for error in &sheet.skipped {
+                println!("{}", error);
+            }
+        }
+        Ok(sheet.rows)
     }   
+    // Why a sheet row did not make it into the combo data, rows are counted from the first data row
+    #[derive(Debug)]
+    pub enum RowError {
+        NotAnArray(usize),
+        NotAnObject(usize),
+        NotText { row: usize, column: usize },
+        NoPieces(usize),
+        // More cards than the ten piece columns hold
+        TooManyPieces { row: usize, pieces: usize },
+    }
+    impl std::fmt::Display for RowError {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                RowError::NotAnArray(row) => write!(f, "Row {} is not a list of cells", row),
+                RowError::NotAnObject(row) => write!(f, "Combo {} is not an object", row),
+                RowError::NotText { row, column } => write!(f, "Row {} column {} is not text", row, column),
+                RowError::NoPieces(row) => write!(f, "Row {} has no combo pieces", row),
+                RowError::TooManyPieces { row, pieces } => write!(f, "Combo {} has {} pieces, only {} fit", row, pieces, PIECES.count()),
+            }
+        }
+    }
+    #[derive(Debug, Default)]
+    pub struct Sheet {
+        pub rows: Vec<Vec<String>>,
+        pub skipped: Vec<RowError>,
+    }
+    // Walks valueRanges[0].values cell by cell, so commas and quotes in card names stay inside their cell
+    pub fn rows(json: &Value) -> CEResult<Sheet> {
+        let values = match json["valueRanges"][0]["values"].as_array() {
+            Some(t) => t,
+            None => return Err(CEerror::ComboError),
+        };
+        let mut sheet = Sheet::default();
+
+        for (row, value) in values.iter().enumerate() {
+            let cells = match value.as_array() {
+                Some(t) => t,
+                None => {
+                    sheet.skipped.push(RowError::NotAnArray(row));
+                    continue;
+                },
+            };
+
+            let mut result = Vec::<String>::new();
+            let mut error = None;
+            for (column, cell) in cells.iter().enumerate() {
+                match cell {
+                    Value::String(t) => result.push(t.to_string()),
+                    Value::Null => result.push(String::new()),
+                    Value::Number(t) => result.push(t.to_string()),
+                    _ => {
+                        error = Some(RowError::NotText { row, column });
+                        break;
+                    },
+                }
+            }
+
+            match error {
+                Some(e) => sheet.skipped.push(e),
+                None if Combo::from_row(&result).is_none() => {
+                    // Trailing empty rows of the sheet are no combos, only count rows with content
+                    if result.iter().any(|cell| !cell.is_empty()) {
+                        sheet.skipped.push(RowError::NoPieces(row));
+                    }
+                },
+                None => sheet.rows.push(result),
+            }
+        }
+        Ok(sheet)
+    }
     fn request_combo() -> CEResult<String> {
 
         println!("Fetching available Combos...");
@@ -342,31 +934,372 @@ pub mod combo {
         };
         request
     }
-    fn combo_to_json(request: &String) -> CEResult<Value> {
+    fn combo_to_json(request: &str) -> CEResult<Value> {
 
         match serde_json::from_str(request) {
             Ok(t) => Ok(t),
             Err(_) => Err(CEerror::ComboError),
         }
     }
-    fn seperate_combos(json: CEResult<Value>) -> Vec<String> {
 
-        let mut vec = Vec::new();
 
-        match json {       
-            Ok(t) =>{
-                vec = t["valueRanges"][0]["values"]
-                    .to_string()
-                    .trim()
-                    //.replace(",\"\"", "")
-                    .split(",[")
-                    .flat_map(str::parse::<String>)
-                    .collect::<Vec<String>>();
+    /* Commander Spellbook exports, either the current {"variants": [...]} format or the older
+       list of {"id", "c", "i", "p", "s", "r"} objects. Both become rows laid out like the sheet */
+    pub mod spellbook {
+        use serde_json::Value;
+        use crate::types::{CEResult, CEerror};
+        use super::{Combo, RowError, Sheet, PIECES, COLOR_IDENTITY, PREREQUISITES, STEPS, RESULTS};
 
-            },
-            Err(e) => println!("{}", e),
+        pub fn rows(json: &Value) -> CEResult<Sheet> {
+            let combos = match json["variants"].as_array().or_else(|| json.as_array()) {
+                Some(t) => t,
+                None => return Err(CEerror::ComboError),
+            };
+            let mut sheet = Sheet::default();
+
+            for (row, combo) in combos.iter().enumerate() {
+                if !combo.is_object() {
+                    sheet.skipped.push(RowError::NotAnObject(row));
+                    continue;
+                }
+
+                let (pieces, result) = if combo["uses"].is_array() { variant(combo) } else { legacy(combo) };
+
+                // A combo cut down to ten cards would look complete without the rest
+                if pieces > PIECES.count() {
+                    sheet.skipped.push(RowError::TooManyPieces { row, pieces });
+                    continue;
+                }
+                match Combo::from_row(&result) {
+                    Some(_) => sheet.rows.push(result),
+                    None => sheet.skipped.push(RowError::NoPieces(row)),
+                }
+            }
+            Ok(sheet)
         }
-        vec
-    } 
+        fn variant(combo: &Value) -> (usize, Vec<String>) {
+            let pieces = list(&combo["uses"], |used| used["card"]["name"].as_str());
+            let results = list(&combo["produces"], |produced| produced["feature"]["name"].as_str());
+
+            let mut prerequisites = Vec::<String>::new();
+            for key in ["manaNeeded", "otherPrerequisites", "easyPrerequisites", "notablePrerequisites"] {
+                if let Some(t) = combo[key].as_str() {
+                    if !t.is_empty() {
+                        prerequisites.push(t.to_string());
+                    }
+                }
+            }
+
+            (pieces.len(), row(text(&combo["id"]), pieces, text(&combo["identity"]), prerequisites.join("\n"), text(&combo["description"]), results.join("\n")))
+        }
+        fn legacy(combo: &Value) -> (usize, Vec<String>) {
+            let pieces = list(&combo["c"], |card| card.as_str());
+
+            (pieces.len(), row(text(&combo["id"]), pieces, text(&combo["i"]), text(&combo["p"]), text(&combo["s"]), text(&combo["r"])))
+        }
+        fn row(id: String, pieces: Vec<String>, identity: String, prerequisites: String, steps: String, results: String) -> Vec<String> {
+            let mut result = vec![String::new(); RESULTS + 1];
+
+            result[0] = id;
+            for (slot, piece) in PIECES.zip(pieces) {
+                result[slot] = piece;
+            }
+            // Identities come as "WUB" as well as "w,u,b"
+            result[COLOR_IDENTITY] = identity.chars().filter(|c| c.is_alphabetic()).map(|c| c.to_string()).collect::<Vec<String>>().join(",");
+            result[PREREQUISITES] = prerequisites;
+            result[STEPS] = steps;
+            result[RESULTS] = results;
+            result
+        }
+        fn list<'a>(value: &'a Value, name: impl Fn(&'a Value) -> Option<&'a str>) -> Vec<String> {
+            match value.as_array() {
+                Some(t) => t.iter().filter_map(name).map(|name| name.to_string()).collect(),
+                None => Vec::new(),
+            }
+        }
+        fn text(value: &Value) -> String {
+            match value {
+                Value::String(t) => t.to_string(),
+                Value::Number(t) => t.to_string(),
+                Value::Array(t) => t.iter().map(text).collect::<Vec<String>>().join("\n"),
+                _ => String::new(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, sync::Once};
+    use crate::config;
+    use crate::types::{Card, Deck};
+    use super::scryfall::{self, FixtureFetch};
+    use super::user_import::{self, Section};
+    use super::mtgo;
+    use super::combo::{Combo, RowError};
+    use crate::types::Colors;
+    use crate::logic::database;
+
+    static SETUP: Once = Once::new();
+
+    fn fixtures() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures")
+    }
+    /* Every test shares one configuration, cards come from tests/fixtures and nothing is downloaded.
+       The data directory lives in target/, each run starts with an empty one and cargo clean removes it */
+    fn setup() -> &'static config::Config {
+        SETUP.call_once(|| {
+            let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target").join("test-data");
+            let _ = fs::remove_dir_all(&dir);
+            let settings = config::Config::new(Some(&dir.to_string_lossy()))
+                .fixtures(Some(&fixtures().to_string_lossy()));
+            config::init(settings).expect("Test data directory can be created");
+        });
+        config::get()
+    }
+
+    #[test]
+    fn fixture_file_names_stay_in_the_directory() {
+        assert_eq!(FixtureFetch::file_name("Fire // Ice"), "fire-ice");
+        assert_eq!(FixtureFetch::file_name("Andúril, Flame of the West"), "anduril-flame-of-the-west");
+        assert_eq!(FixtureFetch::file_name("Thassa's Oracle"), "thassas-oracle");
+    }
+
+    #[test]
+    fn card_make_from_fixture() {
+        let fetch = FixtureFetch { dir: fixtures() };
+
+        let card = Card::make(&scryfall::get_from(&fetch, &String::from("Sol Ring")).unwrap(), false).unwrap();
+        assert_eq!(card.name, "Sol Ring");
+        assert_eq!(card.cmc, 1.0);
+        assert!(card.colors.is_empty());
+
+        let card = Card::make(&scryfall::get_from(&fetch, &String::from("Andúril, Flame of the West")).unwrap(), false).unwrap();
+        assert!(card.legendary);
+    }
+
+    #[test]
+    fn split_card_from_fixture() {
+        let fetch = FixtureFetch { dir: fixtures() };
+
+        let card = Card::make(&scryfall::get_from(&fetch, &String::from("Fire // Ice")).unwrap(), false).unwrap();
+        assert_eq!(card.name, "Fire");
+        assert_eq!(card.backside.map(|t| t.name), Some(String::from("Ice")));
+    }
+
+    #[test]
+    fn deck_make_from_fixtures() {
+        let config = setup();
+        let path = config.decks().join("fixture-deck.txt");
+        fs::write(&path, "1 Llanowar Elves *CMDR*\n1 Sol Ring\n1 Fire // Ice\n1 Andúril, Flame of the West\n").unwrap();
+
+        let deck = Deck::make(path.to_string_lossy().to_string());
+        fs::remove_file(&path).unwrap();
+        let deck = deck.unwrap();
+        assert_eq!(deck.name, "fixture-deck.txt");
+        assert_eq!(deck.commander.len(), 1);
+        assert_eq!(deck.commander[0].name, "Llanowar Elves");
+        assert_eq!(deck.library.len(), 3);
+    }
+
+    #[test]
+    fn mtgo_sideboard_is_no_command_zone() {
+        let mut dek = String::from("<Deck>\n<Cards Quantity=\"98\" Sideboard=\"false\" Name=\"Forest\" Annotation=\"0\" />\n");
+        for i in 0..15 {
+            dek.push_str(&format!("<Cards Quantity=\"1\" Sideboard=\"true\" Name=\"Card {}\" Annotation=\"0\" />\n", i));
+        }
+        dek.push_str("</Deck>\n");
+
+        let entries = mtgo::parse_dek(&dek).unwrap();
+        assert!(entries.iter().all(|entry| entry.section == Section::Deck || entry.section == Section::Sideboard));
+
+        let dek = "<Deck>\n<Cards Quantity=\"99\" Sideboard=\"false\" Name=\"Forest\" Annotation=\"0\" />\n<Cards Quantity=\"1\" Sideboard=\"true\" Name=\"Llanowar Elves\" Annotation=\"0\" />\n</Deck>\n";
+        let entries = mtgo::parse_dek(dek).unwrap();
+        assert_eq!(entries.iter().filter(|entry| entry.section == Section::Commander).count(), 1);
+    }
+
+    #[test]
+    fn dek_round_trip() {
+        let card = |name: &str, commander: bool| Card::make(&scryfall::get_from(&FixtureFetch { dir: fixtures() }, &name.to_string()).unwrap(), commander).unwrap();
+        let mut library = vec![card("Sol Ring", false)];
+        library.extend((0..97).map(|_| card("Forest", false)));
+        let mut deck = Deck::new(String::from("round-trip"), vec![card("Ayli, Eternal Pilgrim", true)], library);
+        deck.companion.push(card("Lurrus of the Dream-Den", false));
+
+        let entries = mtgo::parse_dek(&mtgo::to_dek(&deck)).unwrap();
+        let section = |name: &str| entries.iter().find(|entry| entry.name == name).map(|entry| (entry.quantity, entry.section));
+        assert_eq!(entries.len(), 4);
+        assert_eq!(section("Forest"), Some((97, Section::Deck)));
+        assert_eq!(section("Sol Ring"), Some((1, Section::Deck)));
+        assert_eq!(section("Ayli, Eternal Pilgrim"), Some((1, Section::Commander)));
+        assert_eq!(section("Lurrus of the Dream-Den"), Some((1, Section::MaybeCompanion)));
+    }
+
+    #[test]
+    fn dek_companion_needs_the_keyword() {
+        let config = setup();
+        let path = config.decks().join("companion.dek");
+        fs::write(&path, "<Deck>\n<Cards Quantity=\"97\" Sideboard=\"false\" Name=\"Forest\" Annotation=\"0\" />\n\
+            <Cards Quantity=\"1\" Sideboard=\"true\" Name=\"Ayli, Eternal Pilgrim\" Annotation=\"1\" />\n\
+            <Cards Quantity=\"1\" Sideboard=\"true\" Name=\"Lurrus of the Dream-Den\" Annotation=\"0\" />\n\
+            <Cards Quantity=\"1\" Sideboard=\"true\" Name=\"Sol Ring\" Annotation=\"0\" />\n</Deck>\n").unwrap();
+
+        let deck = Deck::make(path.to_string_lossy().to_string());
+        fs::remove_file(&path).unwrap();
+        let deck = deck.unwrap();
+        assert_eq!(deck.commander.len(), 1);
+        assert_eq!(deck.companion.iter().map(|card| card.name.as_str()).collect::<Vec<&str>>(), vec!["Lurrus of the Dream-Den"]);
+        assert!(deck.library.iter().all(|card| card.name == "Forest"));
+    }
+
+    #[test]
+    fn spellbook_variants_and_legacy_rows() {
+        let json = serde_json::json!({ "variants": [
+            {
+                "id": "123-456",
+                "uses": [{ "card": { "name": "Kiki-Jiki, Mirror Breaker" } }, { "card": { "name": "Zealous Conscripts" } }],
+                "produces": [{ "feature": { "name": "Infinite copies" } }],
+                "identity": "R",
+                "manaNeeded": "{2}{R}{R}",
+                "description": "Tap Kiki-Jiki.\nCopy Zealous Conscripts."
+            },
+            { "id": "789", "c": ["Thassa's Oracle", "Demonic Consultation"], "i": "u,b", "p": "", "s": "Cast Consultation.", "r": "Win the game" },
+            { "id": "empty", "uses": [] },
+            "not a combo"
+        ]});
+
+        let sheet = super::combo::spellbook::rows(&json).unwrap();
+        assert_eq!(sheet.rows.len(), 2);
+        assert_eq!(sheet.skipped.len(), 2);
+
+        let combos: Vec<Combo> = sheet.rows.iter().filter_map(|row| Combo::from_row(row)).collect();
+        assert_eq!(combos[0].pieces, vec!["Kiki-Jiki, Mirror Breaker", "Zealous Conscripts"]);
+        assert_eq!(combos[0].color_identity, vec![Colors::Red]);
+        assert_eq!(combos[0].steps, vec!["Tap Kiki-Jiki.", "Copy Zealous Conscripts."]);
+        assert_eq!(combos[0].results, vec!["Infinite copies"]);
+        assert_eq!(combos[1].pieces, vec!["Thassa's Oracle", "Demonic Consultation"]);
+        assert_eq!(combos[1].color_identity, vec![Colors::Blue, Colors::Black]);
+    }
+
+    #[test]
+    fn spellbook_reports_pieces_past_ten() {
+        let pieces: Vec<String> = (0..11).map(|i| format!("Card {}", i)).collect();
+        let json = serde_json::json!([{ "id": "big", "c": pieces, "i": "g" }]);
+
+        let sheet = super::combo::spellbook::rows(&json).unwrap();
+        assert!(sheet.rows.is_empty());
+        assert!(matches!(sheet.skipped[..], [RowError::TooManyPieces { row: 0, pieces: 11 }]));
+    }
+
+    #[test]
+    fn combo_cells_stay_as_decoded() {
+        let row: Vec<String> = ["1", "Isochron Scepter", "Dramatic Reversal", "", "", "", "", "", "", "", "", "u",
+            "Scepter imprinted with [Dramatic Reversal]", "Activate \"Isochron Scepter\".\nCast the copy.", "Infinite mana"]
+            .iter().map(|cell| cell.to_string()).collect();
+
+        let combo = Combo::from_row(&row).unwrap();
+        assert_eq!(combo.pieces, vec!["Isochron Scepter", "Dramatic Reversal"]);
+        assert_eq!(combo.prerequisites, vec!["Scepter imprinted with [Dramatic Reversal]"]);
+        assert_eq!(combo.steps, vec!["Activate \"Isochron Scepter\".", "Cast the copy."]);
+    }
+
+    #[test]
+    fn sheet_rows_keep_commas_and_count_skipped_rows() {
+        let json = serde_json::json!({ "valueRanges": [{ "values": [
+            ["1", "Kiki-Jiki, Mirror Breaker", "Zealous Conscripts", "", "", "", "", "", "", "", "", "r", "", "Tap Kiki-Jiki, Mirror Breaker.", "Infinite copies"],
+            ["2", "Thassa's Oracle", null, "", "", "", "", "", "", "", "", "u", "", "Cast Thassa's Oracle.", "Win the game"],
+            ["3", "", "", "", "", "", "", "", "", "", "", "g", "", "", "Nothing"],
+            ["4", { "card": "Sol Ring" }],
+            "not a row",
+            ["", "", ""]
+        ]}]});
+
+        let sheet = super::combo::rows(&json).unwrap();
+        assert_eq!(sheet.rows.len(), 2);
+        assert_eq!(sheet.skipped.len(), 3);
+        assert!(matches!(sheet.skipped[..], [RowError::NoPieces(2), RowError::NotText { row: 3, column: 1 }, RowError::NotAnArray(4)]));
 
-}
\ No newline at end of file
+        let combo = Combo::from_row(&sheet.rows[0]).unwrap();
+        assert_eq!(combo.pieces, vec!["Kiki-Jiki, Mirror Breaker", "Zealous Conscripts"]);
+        assert!(combo.prerequisites.is_empty());
+        assert_eq!(combo.steps, vec!["Tap Kiki-Jiki, Mirror Breaker."]);
+        assert_eq!(Combo::from_row(&sheet.rows[1]).unwrap().pieces, vec!["Thassa's Oracle"]);
+    }
+
+    #[test]
+    fn decklist_sections_and_comments() {
+        let list = "About\nName Ayli Aristocrats\n\nCommander\n1 Ayli, Eternal Pilgrim\n\nDeck\n1x Sol Ring\n// Lands\n# basics\n\
+            30 Forest (ZEN) 246\n1 Fire/Ice\n\nSideboard:\n1 Divination\n\nCompanion\n1 Lurrus of the Dream-Den\n";
+
+        let entries = user_import::parse_decklist(list).unwrap();
+        let found: Vec<(u8, &str, Section)> = entries.iter().map(|entry| (entry.quantity, entry.name.as_str(), entry.section)).collect();
+        assert_eq!(found, vec![
+            (1, "Ayli, Eternal Pilgrim", Section::Commander),
+            (1, "Sol Ring", Section::Deck),
+            (30, "Forest", Section::Deck),
+            (1, "Fire // Ice", Section::Deck),
+            (1, "Divination", Section::Sideboard),
+            (1, "Lurrus of the Dream-Den", Section::Companion),
+        ]);
+    }
+
+    #[test]
+    fn decklist_entries() {
+        let entry = user_import::entry("1 Llanowar Elves *CMDR*", Section::Deck).unwrap();
+        assert_eq!((entry.name.as_str(), entry.section), ("Llanowar Elves", Section::Commander));
+
+        let entry = user_import::entry("2X Sol Ring (CMR) 472 *F*", Section::Deck).unwrap();
+        assert_eq!((entry.quantity, entry.name.as_str(), entry.section), (2, "Sol Ring", Section::Deck));
+
+        assert!(user_import::entry("Sol Ring", Section::Deck).is_err());
+        assert!(user_import::entry("1", Section::Deck).is_err());
+        assert!(user_import::entry("1 *CMDR*", Section::Deck).is_err());
+    }
+
+    #[test]
+    fn printings_are_stripped() {
+        assert_eq!(user_import::strip_printing("Sol Ring (CMR) 472"), "Sol Ring");
+        assert_eq!(user_import::strip_printing("Sol Ring (CMR)"), "Sol Ring");
+        assert_eq!(user_import::strip_printing("Forest (PLST) M20-274"), "Forest");
+        // Parentheses that are part of the name stay
+        assert_eq!(user_import::strip_printing("B.F.M. (Big Furry Monster)"), "B.F.M. (Big Furry Monster)");
+        assert_eq!(user_import::strip_printing("Sol Ring"), "Sol Ring");
+    }
+
+    #[test]
+    fn collection_reports_unknown_names() {
+        let fetch = FixtureFetch { dir: fixtures() };
+        let names = vec![String::from("Llanowar Elves"), String::from("Not A Card")];
+
+        let collection = scryfall::collection_from(&fetch, &names);
+        assert!(collection.cards.contains_key("Llanowar Elves"));
+        assert_eq!(collection.unresolved.len(), 1);
+        assert_eq!(collection.unresolved[0].name, "Not A Card");
+    }
+
+    #[test]
+    fn database_index_reads_single_cards() {
+        let config = setup();
+        let fetch = FixtureFetch { dir: fixtures() };
+        let cards: Vec<serde_json::Value> = ["Sol Ring", "Fire // Ice"].iter()
+            .map(|name| serde_json::from_str(&scryfall::get_from(&fetch, &name.to_string()).unwrap()).unwrap())
+            .collect();
+
+        database::save(&serde_json::Value::Array(cards)).unwrap();
+        let index = database::load_index().expect("Fresh index fits the database");
+        assert_eq!(database::get("sol ring", &index).unwrap()["name"], "Sol Ring");
+        assert_eq!(database::get("Ice", &index).unwrap()["name"], "Fire // Ice");
+        assert!(database::get("Not A Card", &index).is_err());
+
+        // A database that changed after indexing needs a new index
+        fs::write(config.database(), "[]").unwrap();
+        assert!(database::load_index().is_none());
+    }
+
+    #[test]
+    fn scryfall_client_is_built_once() {
+        let first = scryfall::client().unwrap();
+        let second = scryfall::client().unwrap();
+        assert!(std::ptr::eq(first, second));
+    }
+}
@@ -1,144 +1,74 @@
 /******************************* Functions for Threads **************************************/
 pub mod thread_fn {
-    use std::{thread, sync::{mpsc, Arc}, time::Duration};
-    use crate::{import::{user_import::{quantity_card, decklist}, self }, types::CEResult};
-    use crate::types::{Card, Deck};
-    use crate::logic::database;
+    use std::collections::HashMap;
+    use serde_json::Value;
+    use crate::{import::{user_import::{decklist, DeckEntry, Section}, self }, types::CEResult};
+    use crate::types::{Card, CardFields, Deck, Keywords};
+    use crate::logic::database::{self, Index};
+    use crate::config;
 
     pub fn deck(input: String) -> CEResult<Deck> {
-        use serde_json::Value;
 
-        let mut deck = Deck::new(String::from(&input), Vec::<Card>::new(), Vec::<Card>::new(), );
+        let mut deck = Deck::new(config::deck_name(&input), Vec::<Card>::new(), Vec::<Card>::new(), );
 
-        match decklist(input) {
+        let entries = decklist(input)?;
 
-            Ok(t) => {
-                let (tx, rx) = mpsc::channel();
-                let tx1 = tx.clone();
-                let tx2 = tx.clone();
-                let tx3 = tx.clone();
-
-                let tasks = Arc::new(t);
-                let tasks_arc_clone1 = Arc::clone(&tasks);
-                let tasks_arc_clone2 = Arc::clone(&tasks);
-                let tasks_arc_clone3 = Arc::clone(&tasks);
-
-                let quater_one = tasks.len() / 4;
-                let quater_two = tasks.len() / 2;
-                let quater_three = 3 * tasks.len() / 4;          
-
-                // Little hack to pass through a valid Value to get the API function when load failed
-                let replace: Value = serde_json::from_str("{\"value\": \"Database not loaded\"}").expect("Fatal error: Can not build replacement json");
-
-                let database_unwrap: Value = match database::load() {
-                    Ok(t) => t,
-                    Err(_) => {
-                        println!("Can not open database, threads default to api request");
-                        replace
-                    },
-                };
-
-                let database = Arc::new(database_unwrap);
-                let database_arc_clone1 = Arc::clone(&database);
-                let database_arc_clone2 = Arc::clone(&database);
-                let database_arc_clone3 = Arc::clone(&database);
-
-                let handle1 = thread::spawn(move || {
-                    for i in 0..quater_one{
-                        thread_card_make(&tasks_arc_clone1, &tx, &i, &database_arc_clone1);
-                    }        
-                });
-                let handle2 = thread::spawn(move || { 
-                    for i in quater_one..quater_two {
-                        thread_card_make(&tasks_arc_clone2, &tx1, &i, &database_arc_clone2); 
-                    } 
-                });
-                let handle3 = thread::spawn(move || {
-                    for i in quater_two..quater_three {
-                        thread_card_make(&tasks_arc_clone3, &tx2, &i, &database_arc_clone3);
-                    } 
-                });
-                
-                for i in quater_three..tasks.len() {
-                    thread_card_make(&tasks, &tx3, &i, &database);
-                } 
-                
-
-                drop(tx3);
-                
-                for card in rx {
-                    if !card.commander{
-                        deck.library.push(card);
-                    } else {
-                        deck.commander.push(card);
-                    } 
-                }
-                handle1.join().expect("Can not join thread");
-                handle2.join().expect("Can not join thread");
-                handle3.join().expect("Can not join thread");
-                return Ok(deck);
+        // An empty index sends every card to the api
+        let empty = Index::default();
+        let index = match database::load() {
+            Ok(t) => t,
+            Err(_) => {
+                println!("Can not open database, default to api request");
+                &empty
             },
-            Err(e) => return Err(e),
-        }
-    }
-    fn thread_card_make_api(decklist: &Arc<Vec<String>> , tx: &mpsc::Sender<Card> , i: &usize) {
-        let mut commander: bool = false;
-        let mut quantity_card = quantity_card(&decklist[*i]).expect("Incompatible decklist format");
+        };
 
-        if quantity_card[1].contains("*CMDR*") {
-            commander = true;
-            quantity_card[1] = quantity_card[1].replace(" *CMDR*", "");
-        }
+        // Sideboard and maybeboard are not part of the 100 cards
+        let entries: Vec<&DeckEntry> = entries.iter().filter(|entry| entry.section != Section::Sideboard).collect();
 
-        match import::scryfall::get(&quantity_card[1]) {
-            Ok(t) => {
-                match Card::make(&t, commander) {
-                    Ok(t) => {
-                        println!("Scryfall: {} {}",&quantity_card[0], t.name);
-                        for _j in 0..quantity_card[0].parse::<u8>().expect("List format error: No integer.") {   
-                            tx.send(t.clone()).expect("Thread can not send.");
-                        }    
-                        thread::sleep(Duration::from_millis(10))
-                    },
-                    Err(e) => println!("Thread error detected: {}", e),
-                }
-            },
-            Err(e) => println!("Thread error detected: {}", e),
-        }
-    }
-    fn thread_card_make(decklist: &Arc<Vec<String>> , tx: &mpsc::Sender<Card> , i: &usize, database: &Arc<serde_json::Value>){ 
-        let mut commander: bool = false;
-        let mut quantity_card = quantity_card(&decklist[*i]).expect("Incompatible decklist format");
+        let missing: Vec<String> = entries.iter()
+            .filter(|entry| database::get(&entry.name, index).is_err())
+            .map(|entry| entry.name.to_string())
+            .collect();
 
+        let mut requested = HashMap::<String, Value>::new();
+        if !missing.is_empty() {
+            println!("Request {} cards from scryfall", missing.len());
+            let collection = import::scryfall::collection(&missing);
 
-        if quantity_card[1].contains("*CMDR*") {
-            commander = true;
-            quantity_card[1] = quantity_card[1].replace(" *CMDR*", "");
+            for unresolved in &collection.unresolved {
+                println!("Can not resolve {}: {}", unresolved.name, unresolved.reason);
+            }
+            requested = collection.cards;
         }
 
-        if quantity_card[1].contains("/") && !quantity_card[1].contains("//") {
-            quantity_card[1] = quantity_card[1].replace("/", "//");
-        }
+        for entry in entries {
+            let (source, found) = match database::get(&entry.name, index) {
+                Ok(t) => ("Database", t),
+                Err(_) => match requested.get(&entry.name) {
+                    Some(t) => ("Scryfall", t.clone()),
+                    None => continue,
+                },
+            };
 
- 
-        match database::get(&quantity_card[1], &database) {
-            Ok(t) => {
-                match Card::make(&t.to_string(), commander) {
-                    Ok(t) => {
-                        println!("Database: {} {}",&quantity_card[0], t.name);
-                        for _j in 0..quantity_card[0].parse::<u8>().expect("List format error: No integer.") {   
-                            tx.send(t.clone()).expect("Thread can not send");
+            match Card::make(&found.to_string(), entry.section == Section::Commander) {
+                Ok(card) if entry.section == Section::MaybeCompanion && !card.contains(Keywords::Companion, CardFields::Keywords) => {
+                    println!("Sideboard: {} {}", &entry.quantity, card.name);
+                },
+                Ok(card) => {
+                    println!("{}: {} {}", source, &entry.quantity, card.name);
+                    for _j in 0..entry.quantity {
+                        match entry.section {
+                            Section::Commander => deck.commander.push(card.clone()),
+                            Section::Companion | Section::MaybeCompanion => deck.companion.push(card.clone()),
+                            _ => deck.library.push(card.clone()),
                         }
-                        //thread::sleep(Duration::from_millis(10))
-                    },
-                    Err(e) => println!("Thread error detected: {}", e),
-                } 
-            },
-            Err(_) => {
-                
-                thread_card_make_api(decklist, tx, i);
-            },
-        } 
+                    }
+                },
+                Err(e) => println!("Can not build {}: {}", entry.name, e),
+            }
+        }
+        Ok(deck)
     }
 }
 /******************************* Logic to build a Card **************************************/
@@ -149,9 +79,9 @@ pub mod card_build {
 
     pub fn build(v: &serde_json::Value, commander: bool, mdfc: Option<serde_json::Value>) -> Card {
         
-        Card{
-            cmc: cmc(v["cmc"].to_string()),
-            mana_cost: mana_cost(v["mana_cost"].to_string()),
+        let mut card = Card{
+            cmc: cmc(v),
+            mana_cost: ManaCost::parse(v["mana_cost"].as_str().unwrap_or("")),
             name: name(v["name"].to_string()),
             cardtype: cardtype(v["type_line"].to_string()),
             legendary: legendary(v["type_line"].to_string()),
@@ -159,47 +89,116 @@ pub mod card_build {
             commander: commander,
             backside: backside(mdfc),
             oracle_text: oracle_text(v["oracle_text"].to_string()),
-            keys: keys(v["oracle_text"].to_string()),
-            zones: zones(v["oracle_text"].to_string()),
-            keywords: keywords(v["oracle_text"].to_string()),
+            keys: None,
+            zones: None,
+            keywords: None,
             oracle_types: oracle_types(v["oracle_text"].to_string()),
-            restrictions: restrictions(v["oracle_text"].to_string()),
+            restrictions: None,
+            colors: Vec::new(),
+            color_identity: Vec::new(),
+            abilities: Vec::new(),
+            words: OracleWords::default(),
+        };
+        reparse(&mut card);
+        card.colors = match v["colors"].as_array() {
+            Some(t) => t.iter().filter_map(|color| color.as_str().and_then(|c| c.chars().next()).and_then(Colors::from_symbol)).collect(),
+            None => colors(&card, &color_indicator(v)),
+        };
+        card.color_identity = color_identity(&card, &color_indicator(v));
+        card
+    }
+    // Splits the oracle text into abilities, the card flags are everything its abilities found
+    pub fn reparse(card: &mut Card) {
+        let spell = card.contains(CardType::Instant(None), CardFields::CardType) || card.contains(CardType::Sorcery(None), CardFields::CardType);
+        card.abilities = super::oracle::abilities(&card.oracle_text, spell);
+
+        card.keys = flags(card.abilities.iter().flat_map(|ability| ability.keys.iter()));
+        card.zones = flags(card.abilities.iter().flat_map(|ability| ability.zones.iter()));
+        card.keywords = flags(card.abilities.iter().flat_map(|ability| ability.keywords.iter()));
+        card.restrictions = flags(card.abilities.iter().flat_map(|ability| ability.restrictions.iter()));
+
+        if let Some(backside) = &mut card.backside {
+            if backside.abilities.is_empty() {
+                reparse(backside);
+            }
         }
     }
-    fn name(input: String) -> String {  
-        input.replace("\"", "")
+    fn flags<'a, T: PartialEq + Copy + 'a>(found: impl Iterator<Item = &'a T>) -> Option<Vec<T>> {
+        let mut result = Vec::<T>::new();
+
+        for flag in found {
+            if !result.contains(flag) {
+                result.push(*flag);
+            }
+        }
+        if result.is_empty() { None } else { Some(result) }
     }
-    fn cmc(input: String) -> f32 {
+    // The card's colors from its mana cost and color indicator, scryfall leaves them out on double faced cards
+    pub fn colors(card: &Card, indicator: &[Colors]) -> Vec<Colors> {
+        Colors::identity().iter()
+            .filter(|color| indicator.contains(color) || card.mana_cost.pips(**color) > 0)
+            .copied()
+            .collect()
+    }
+    // Mana cost, color indicator and mana symbols in the rules text of both faces, reminder text does not count
+    pub fn color_identity(card: &Card, indicator: &[Colors]) -> Vec<Colors> {
+        let mut found = indicator.to_vec();
+
+        let mut text = format!("{} {}", card.mana_cost, without_reminder(&card.oracle_text));
+        if let Some(backside) = &card.backside {
+            text = format!("{} {} {}", text, backside.mana_cost, without_reminder(&backside.oracle_text));
+            found.extend(&backside.color_identity);
+        }
 
-        let mut i: f32 = 0.0;
-        for colour in Colors::iter() {
-            if input.contains(&colour.to_string()) {
-                i += 1.0;
+        for symbol in text.split('{').skip(1) {
+            let symbol = symbol.split('}').next().unwrap_or("");
+            for part in symbol.split('/') {
+                if part.len() == 1 {
+                    if let Some(color) = part.chars().next().and_then(Colors::from_symbol) {
+                        found.push(color);
+                    }
+                }
             }
         }
 
-        // Prepare from {4}{R}{G} to 4 R G
-        let mana = &input
-        .replace("{", "")
-        .replace("}", " ")
-        .replace("\"", "")
-        .trim()
-        .split(" ")
-        .flat_map(str::parse::<String>)
-        .collect::<Vec<String>>();
+        Colors::identity().iter().filter(|color| found.contains(color)).copied().collect()
+    }
+    fn color_indicator(v: &serde_json::Value) -> Vec<Colors> {
+        let mut result = Vec::<Colors>::new();
 
-        // Check if first char is number, if yes add colour
-        match mana[0].parse::<f32>(){
-            Ok(t) => {
-                i += t; 
-                return i;
-            },
-            Err(_) => return i,
-        } 
+        if let Some(indicator) = v["color_indicator"].as_array() {
+            for color in indicator {
+                if let Some(color) = color.as_str().and_then(|t| t.chars().next()).and_then(Colors::from_symbol) {
+                    result.push(color);
+                }
+            }
+        }
+        result
+    }
+    fn without_reminder(text: &str) -> String {
+        let mut result = String::new();
+        let mut depth = 0;
+
+        for c in text.chars() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth = (depth - 1).max(0),
+                _ if depth == 0 => result.push(c),
+                _ => (),
+            }
+        }
+        result
     }
-    fn mana_cost(input: String) -> String {
+    fn name(input: String) -> String {  
         input.replace("\"", "")
     }
+    // Scryfall knows the mana value, faces of split and double faced cards only bring their mana cost
+    fn cmc(v: &serde_json::Value) -> f32 {
+        match v["cmc"].as_f64() {
+            Some(t) => t as f32,
+            None => ManaCost::parse(v["mana_cost"].as_str().unwrap_or("")).mana_value(),
+        }
+    }
     fn cardtype(input: String) -> Vec<CardType> {
         let mut cardtype: Vec<CardType> = Vec::new();
         let mut result: Vec<CardType> = Vec::new();
@@ -255,51 +254,6 @@ pub mod card_build {
     fn oracle_text(input: String) -> String { // not neccessary, but maybe need to build something here
         input
     }
-    fn keys(input: String) -> Option<Vec<Keys>> {
-        let mut result: Vec<Keys> = Vec::new();
-
-        for key in Keys::iter() {
-            if input.to_lowercase().contains(&key.to_string().to_lowercase().replace("_", " ")) {
-                result.push(key);
-            }
-        }
-
-        if result.len() != 0 {
-            return Some(result);
-        }else {
-            return None;
-        }
-    }
-    fn zones(input: String) -> Option<Vec<Zones>> {
-        let mut result: Vec<Zones> = Vec::new();
-        
-        for zone in Zones::iter() {
-            if input.to_lowercase().contains(&zone.to_string().to_lowercase()) {
-                result.push(zone); 
-            }
-        }
-        
-        if result.len() != 0 {
-            return Some(result);
-        }else {
-            return None;
-        }
-    }
-    fn keywords(input: String) -> Option<Vec<Keywords>> {
-        let mut result: Vec<Keywords> = Vec::new();
-
-        for keyword in Keywords::iter() {
-            if input.to_lowercase().contains(&keyword.to_string().to_lowercase().replace("_", " ")) {
-                result.push(keyword); 
-            }
-        }
-
-        if result.len() != 0 {
-            return Some(result);
-        }else {
-            return None;
-        }
-    }
     fn oracle_types(input: String) -> Option<Vec<CardType>> {
         let mut result: Vec<CardType> = Vec::new();
         let mut buffer: Vec<CardType> = Vec::new();
@@ -340,21 +294,6 @@ pub mod card_build {
             return None;
         }
     }
-    fn restrictions(input: String ) -> Option<Vec<Restrictions>> {
-        let mut result: Vec<Restrictions> = Vec::new();
-
-        for restriction in Restrictions::iter() {
-            if input.to_lowercase().contains(&restriction.to_string().to_lowercase().replace("_", " ")) {
-                result.push(restriction); 
-            }
-        }
-
-        if result.len() != 0 {
-            return Some(result);
-        }else {
-            return None;
-        } 
-    }
     fn get_type(input: &String, cardtype: CardType) -> CardType {
         match cardtype {
             CardType::Creature(_) => {
@@ -437,6 +376,7 @@ pub mod card_build {
             
             },
             CardType::Planeswalker => { return CardType::Planeswalker },
+            CardType::Conspiracy => { return CardType::Conspiracy },
             CardType::Token => { return CardType::Token },
             CardType::Basic => { return CardType::Basic },
             CardType::Card => { return CardType::Card },
@@ -444,55 +384,305 @@ pub mod card_build {
         }
     }
 }
+/******************************** Oracle Text Parser ****************************************/
+pub mod oracle {
+    use strum::IntoEnumIterator;
+    use crate::types::*;
+
+    // Endings a word may carry and still count, "draw" finds "draws" and "tap" finds "tapped"
+    const SUFFIXES: [&str; 7] = ["s", "es", "ed", "d", "ing", "n", "'s"];
+    const TRIGGERS: [&str; 3] = ["when", "whenever", "at"];
+    /* The longest real costs like "{2}, {T}, Sacrifice two artifacts and a creature" stay below this many tokens,
+       a longer text before the colon is a sentence like "... gains the following ability: ..." */
+    const MAX_COST_TOKENS: usize = 12;
+
+    /* One ability per line of the oracle text, reminder text removed. Lines of instants and sorceries are spells,
+       otherwise a leading when/whenever/at makes a trigger, a cost before the colon an activated ability and
+       instead/would/enters tapped a replacement effect. */
+    pub fn abilities(oracle_text: &str, spell: bool) -> Vec<Ability> {
+        let text = unescape(oracle_text);
+        let mut result = Vec::<Ability>::new();
+
+        for line in text.lines() {
+            let line = without_reminder(line);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let tokens = words(line);
+            let first = tokens.first().cloned().unwrap_or_default();
+
+            let (kind, cost, effect) = if TRIGGERS.contains(&first.as_str()) {
+                (AbilityKind::Triggered, None, line.to_string())
+            } else if let Some((cost, effect)) = activation(line) {
+                (AbilityKind::Activated, Some(cost), effect)
+            } else if spell {
+                (AbilityKind::Spell, None, line.to_string())
+            } else if ["instead", "would", "enters the battlefield tapped", "enters tapped"].iter().any(|phrase| matches_words(&tokens, phrase, true)) {
+                (AbilityKind::Replacement, None, line.to_string())
+            } else {
+                (AbilityKind::Static, None, line.to_string())
+            };
+
+            result.push(Ability {
+                kind,
+                keys: Keys::iter().filter(|key| matches_words(&tokens, &key.to_string(), true)).collect(),
+                zones: Zones::iter().filter(|zone| contains(&effect, &zone.to_string())).collect(),
+                keywords: Keywords::iter().filter(|keyword| matches_words(&tokens, &keyword.to_string(), true)).collect(),
+                restrictions: Restrictions::iter().filter(|restriction| matches_words(&tokens, &restriction.to_string(), true)).collect(),
+                cost,
+                text: line.to_string(),
+            });
+        }
+        result
+    }
+    // Whole token match of a phrase, case insensitive, words may be inflected
+    pub fn contains(text: &str, phrase: &str) -> bool {
+        matches(text, phrase, true)
+    }
+    // Contiguous whole tokens, "Land" finds "land" but not "Island", inflect also accepts "lands"
+    pub fn matches(text: &str, phrase: &str, inflect: bool) -> bool {
+        matches_words(&words(text), phrase, inflect)
+    }
+    // Same on text already split by words, for texts that are searched many times
+    pub fn matches_words(text: &[String], phrase: &str, inflect: bool) -> bool {
+        let phrase = words(&phrase.replace('_', " "));
+
+        // Like str::contains the empty phrase is in every text
+        if phrase.is_empty() {
+            return true;
+        }
+        if phrase.len() > text.len() {
+            return false;
+        }
+        (0..=text.len() - phrase.len()).any(|start| {
+            phrase.iter().enumerate().all(|(i, word)| text[start + i] == *word || (inflect && same_word(&text[start + i], word)))
+        })
+    }
+    /* Oracle templating, the card refers to itself as CARDNAME. Legends use the part before the comma
+       and every face of a split or double faced card counts. Names are only replaced as whole words,
+       a short name inside a longer word stays */
+    pub fn templated(text: &str, name: &str) -> String {
+        let mut names: Vec<&str> = vec![name];
+        names.extend(name.split(" // "));
+        names.extend(name.split(" // ").filter_map(|face| face.split_once(',').map(|(short, _)| short)));
+        names.sort_by_key(|name| std::cmp::Reverse(name.len()));
+
+        let mut result = unescape(text);
+        for name in names.iter().filter(|name| !name.trim().is_empty()) {
+            result = replace_word(&result, name, "CARDNAME");
+        }
+        result
+    }
+    fn replace_word(text: &str, word: &str, with: &str) -> String {
+        let mut result = String::new();
+        let mut last = 0;
+
+        for (start, _) in text.match_indices(word) {
+            let end = start + word.len();
+            // Overlaps an earlier match
+            if start < last {
+                continue;
+            }
+            let before = text[..start].chars().next_back().map(char::is_alphanumeric).unwrap_or(false);
+            let after = text[end..].chars().next().map(char::is_alphanumeric).unwrap_or(false);
+            if before || after {
+                continue;
+            }
+            result.push_str(&text[last..start]);
+            result.push_str(with);
+            last = end;
+        }
+        result.push_str(&text[last..]);
+        result
+    }
+    // Lowercase tokens, what matching compares
+    pub fn words(text: &str) -> Vec<String> {
+        tokenize(text).iter().map(|token| token.to_lowercase()).collect()
+    }
+    /* Mana symbols like {T} or {2/W} are one token, words keep their apostrophes. Hyphens split words,
+       so "non-Human" still finds "non", every other character that is not whitespace is a token on its own */
+    pub fn tokenize(text: &str) -> Vec<String> {
+        let mut tokens = Vec::<String>::new();
+        let mut chars = text.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c == '{' {
+                let mut symbol = String::from(c);
+                for next in chars.by_ref() {
+                    symbol.push(next);
+                    if next == '}' {
+                        break;
+                    }
+                }
+                tokens.push(symbol);
+            } else if c.is_alphanumeric() {
+                let mut word = String::from(c);
+                while let Some(next) = chars.peek() {
+                    if next.is_alphanumeric() || *next == '\'' || *next == '’' {
+                        word.push(if *next == '’' { '\'' } else { *next });
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(word);
+            } else if !c.is_whitespace() {
+                tokens.push(c.to_string());
+            }
+        }
+        tokens
+    }
+    fn same_word(token: &str, word: &str) -> bool {
+        if token == word {
+            return true;
+        }
+        // Numbers and symbols have no inflection, "1" is not "10"
+        if word.chars().count() < 3 || !word.chars().all(char::is_alphabetic) {
+            return false;
+        }
+        match token.strip_prefix(word) {
+            Some(rest) => SUFFIXES.contains(&rest)
+                // tapped, untapping
+                || match rest.chars().next() {
+                    Some(c) => rest.len() > 2 && word.ends_with(c) && SUFFIXES.contains(&&rest[c.len_utf8()..]),
+                    None => false,
+                },
+            // sacrificing, dying is left out
+            None => word.ends_with('e') && token.strip_prefix(&word[..word.len() - 1]) == Some("ing"),
+        }
+    }
+    // Costs come before the first colon, loyalty abilities start with +N, −N or 0
+    fn activation(line: &str) -> Option<(String, String)> {
+        let (cost, effect) = line.split_once(':')?;
+        let cost = cost.trim();
+        let tokens = tokenize(cost);
+
+        let is_cost = cost.contains('{')
+            || ["+", "−", "-", "0"].iter().any(|start| cost.starts_with(start))
+            || tokens.first().map(|first| ["sacrifice", "discard", "pay", "exile", "remove", "return", "tap", "untap", "put"].contains(&first.to_lowercase().as_str())).unwrap_or(false);
+        // Chapter, level and modal headers are no costs
+        if !is_cost || tokens.len() > MAX_COST_TOKENS {
+            return None;
+        }
+        Some((cost.to_string(), effect.trim().to_string()))
+    }
+    // Card::oracle_text keeps the json quoting of scryfall
+    pub fn unescape(text: &str) -> String {
+        match serde_json::from_str::<String>(text) {
+            Ok(t) => t,
+            Err(_) => text.replace("\\n", "\n").trim_matches('"').to_string(),
+        }
+    }
+    fn without_reminder(text: &str) -> String {
+        let mut result = String::new();
+        let mut depth = 0;
+
+        for c in text.chars() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth = (depth - 1).max(0),
+                _ if depth == 0 => result.push(c),
+                _ => (),
+            }
+        }
+        result
+    }
+}
 /******************************** Database functions ****************************************/
 pub mod database{
     use crate::types::{CEerror, CEResult};
-    use std::{fs::{self, *}, io::{prelude::*, BufReader}, time::{SystemTime, Duration}, ops::Add};
+    use std::{collections::HashMap, fs::{self, *}, io::{prelude::*, BufReader, BufWriter, SeekFrom}, time::{SystemTime, Duration}, ops::Add, sync::OnceLock};
+    use serde::{Serialize, Deserialize};
     use serde_json::Value;
-    use crate::import;
-    
-    pub fn save(input: &String) -> CEResult<()> {
-        
-        let v: Value = serde_json::from_str(&input).expect("Can not create json");
-        serde_json::to_writer(&File::create("database.txt").expect("Can not write database.txt"),&v).expect("Can not write database.txt"); 
-       
-        Ok(())
-    }
-    pub fn load() -> CEResult<serde_json::Value> {
-        let mut contents = String::new();
+    use crate::{import, config};
 
-        println!("Open database from system");
+    // Normalized card name to the position of the card in the database, split cards are reachable by both face names
+    pub type Positions = HashMap<String, usize>;
 
-        match File::open("database.txt") {
-            Ok(t) => {
-                let mut buf_reader = BufReader::new(t);
-                buf_reader.read_to_string(&mut contents).expect("Can not open database");
+    #[derive(Debug)]
+    enum Cards {
+        // Parsed cards, like the data of a collection answer
+        Memory(Vec<Value>),
+        // Start and length of every card in database.txt, a lookup only parses the card it reads
+        File(Vec<(u64, usize)>),
+    }
 
-                let result: Value = serde_json::from_str(&contents).expect("Json not properly build"); 
+    #[derive(Debug)]
+    pub struct Index {
+        cards: Cards,
+        positions: Positions,
+    }
+    impl Index {
+        pub fn new(cards: Vec<Value>) -> Index {
+            let positions = positions(&cards);
+            Index { cards: Cards::Memory(cards), positions }
+        }
+    }
+    impl Default for Index {
+        fn default() -> Index {
+            Index::new(Vec::new())
+        }
+    }
+    // index.txt holds where each card is, the size tells whether it still fits database.txt
+    #[derive(Serialize, Deserialize)]
+    struct IndexFile {
+        bytes: u64,
+        spans: Vec<(u64, usize)>,
+        positions: Positions,
+    }
+
+    // Database and index are read once per run, --register shares them between decks
+    static INDEX: OnceLock<Index> = OnceLock::new();
 
-                println!("Database successfully opened"); 
+    pub fn save(database: &Value) -> CEResult<()> {
+        let cards = database.as_array().map(|t| t.as_slice()).unwrap_or_default();
+        write(cards)
+    }
+    pub fn load() -> CEResult<&'static Index> {
+        if let Some(t) = INDEX.get() {
+            return Ok(t);
+        }
+        println!("Open database from system");
 
-                Ok(result) 
+        let index = match load_index() {
+            Some(t) => t,
+            // Missing, corrupt or older than the database, written again with a fresh index
+            None => {
+                let cards = load_database()?;
+                let modified = fs::metadata(config::get().database()).and_then(|t| t.modified());
+
+                write(&cards)?;
+                // Keep the download time, update() decides on it when the database expires
+                if let Ok(time) = modified {
+                    let _ = File::options().write(true).open(config::get().database()).and_then(|t| t.set_modified(time));
+                }
+                println!("Database successfully indexed");
+                Index::new(cards)
             },
-            Err(_) => Err(CEerror::DatabaseError),
-        }       
+        };
+
+        println!("Database successfully opened"); 
+
+        Ok(INDEX.get_or_init(|| index))
     }
     pub fn update() {
         
         println!("Updating or creating local card library");
 
-        let file = File::open("database.txt");
+        let database = config::get().database();
+        let file = File::open(&database);
         
         match file {
             Ok(_) => {
-                let metadata = fs::metadata("database.txt").expect("File found but can not open");
+                let metadata = fs::metadata(&database).expect("File found but can not open");
                 let now = SystemTime::now();
                 
                 if let Ok(time) = metadata.modified() {
                     // Update every full day
                     if time.add(Duration::from_secs(86400)) <= now {
                         println!("File is older than a day: Update....");
-                        match remove_file("database.txt"){
+                        match remove_file(&database){
                             Ok(_) => println!("Expired database removed..."),
                             Err(_) => println!("Can not remove old database..."),
                         }
@@ -514,26 +704,210 @@ pub mod database{
 
 
     }
-    pub fn get<'a>(input: &String, database: &'a serde_json::Value) -> CEResult<&'a Value> {
-        
-        // Estimated length of library plus a few thousand. Faster than constructing length. 
-        let data_len = 30000; 
-        let art_card = format!("{} // {}", input, input);
+    pub fn get(input: &str, index: &Index) -> CEResult<Value> {
+        let position = *index.positions.get(&normalize(input)).ok_or(CEerror::CardNotFound)?;
+
+        match &index.cards {
+            Cards::Memory(cards) => cards.get(position).cloned().ok_or(CEerror::CardNotFound),
+            Cards::File(spans) => {
+                let (start, length) = *spans.get(position).ok_or(CEerror::CardNotFound)?;
+                let mut file = File::open(config::get().database()).map_err(|_| CEerror::DatabaseError)?;
+                let mut card = vec![0; length];
+
+                file.seek(SeekFrom::Start(start)).map_err(|_| CEerror::DatabaseError)?;
+                file.read_exact(&mut card).map_err(|_| CEerror::DatabaseError)?;
+                serde_json::from_slice(&card).map_err(|_| CEerror::DatabaseError)
+            },
+        }
+    }
+    // Case and accent insensitive key, "Lim-Dûl's Vault" and "lim-dul's vault" are the same card
+    pub fn normalize(name: &str) -> String {
+        let mut result = String::new();
+
+        for c in name.trim().to_lowercase().chars() {
+            match c {
+                'á' | 'à' | 'â' | 'ä' | 'ã' | 'å' => result.push('a'),
+                'é' | 'è' | 'ê' | 'ë' => result.push('e'),
+                'í' | 'ì' | 'î' | 'ï' => result.push('i'),
+                'ó' | 'ò' | 'ô' | 'ö' | 'õ' => result.push('o'),
+                'ú' | 'ù' | 'û' | 'ü' => result.push('u'),
+                'ñ' => result.push('n'),
+                'ç' => result.push('c'),
+                'æ' => result.push_str("ae"),
+                _ => result.push(c),
+            }
+        }
+        result
+    }
+    // Index over a card list like the data of a collection answer
+    pub fn index(database: &Value) -> Index {
+        Index::new(database.as_array().cloned().unwrap_or_default())
+    }
+    fn positions(cards: &[Value]) -> Positions {
+        let mut result = Positions::new();
 
+        for (position, card) in cards.iter().enumerate() {
+            if let Some(name) = card["name"].as_str() {
+                let faces: Vec<&str> = name.split(" // ").collect();
 
+                // Art series cards are named "Card // Card" and shadow the real card
+                if faces.len() == 2 && faces[0] == faces[1] {
+                    continue;
+                }
+                result.entry(normalize(name)).or_insert(position);
+            }
+        }
+        // Face names second, a full card name always wins over a face of the same name
+        for (position, card) in cards.iter().enumerate() {
+            if let Some(name) = card["name"].as_str() {
+                let faces: Vec<&str> = name.split(" // ").collect();
 
-        for i in 0..data_len {
-            match database[i].get("name") {
-                Some(t) => { 
-                    if t.to_string().replace("\"", "") == *input 
-                    && !t.to_string().contains(&art_card) {
-                       return Ok(&database[i]); 
-                    }
-                },
-                None => (),
-            
+                if faces.len() < 2 || faces[0] == faces[1] {
+                    continue;
+                }
+                for face in faces {
+                    result.entry(normalize(face)).or_insert(position);
+                }
             }
         }
-        return Err(CEerror::CardNotFound);
+        result
     }
-}
\ No newline at end of file
+    fn load_database() -> CEResult<Vec<Value>> {
+        let mut contents = String::new();
+
+        match File::open(config::get().database()) {
+            Ok(t) => {
+                let mut buf_reader = BufReader::new(t);
+                buf_reader.read_to_string(&mut contents).map_err(|_| CEerror::DatabaseError)?;
+
+                match serde_json::from_str(&contents) {
+                    Ok(Value::Array(t)) => Ok(t),
+                    _ => Err(CEerror::DatabaseError),
+                }
+            },
+            Err(_) => Err(CEerror::DatabaseError),
+        }
+    }
+    // One card per line inside the json array, so the index can point at every card
+    fn write(cards: &[Value]) -> CEResult<()> {
+        let mut database = BufWriter::new(File::create(config::get().database()).map_err(|_| CEerror::DatabaseError)?);
+        let mut spans = Vec::<(u64, usize)>::new();
+        let mut bytes: u64 = 0;
+        let mut push = |text: &str, database: &mut BufWriter<File>| -> CEResult<u64> {
+            database.write_all(text.as_bytes()).map_err(|_| CEerror::DatabaseError)?;
+            bytes += text.len() as u64;
+            Ok(bytes)
+        };
+
+        push("[\n", &mut database)?;
+        for (i, card) in cards.iter().enumerate() {
+            let card = serde_json::to_string(card).map_err(|_| CEerror::DatabaseError)?;
+            let end = push(&card, &mut database)?;
+            spans.push((end - card.len() as u64, card.len()));
+            push(if i + 1 < cards.len() { ",\n" } else { "\n" }, &mut database)?;
+        }
+        let bytes = push("]\n", &mut database)?;
+        database.flush().map_err(|_| CEerror::DatabaseError)?;
+
+        let file = File::create(config::get().index()).map_err(|_| CEerror::DatabaseError)?;
+        serde_json::to_writer(file, &IndexFile { bytes, spans, positions: positions(cards) }).map_err(|_| CEerror::DatabaseError)
+    }
+    // Index over database.txt without parsing it, None when the index has to be built again
+    pub(crate) fn load_index() -> Option<Index> {
+        let config = config::get();
+        let database = fs::metadata(config.database()).ok()?;
+        let index = fs::metadata(config.index()).ok()?;
+
+        if index.modified().ok()? < database.modified().ok()? {
+            return None;
+        }
+        let file: IndexFile = serde_json::from_reader(BufReader::new(File::open(config.index()).ok()?)).ok()?;
+
+        if file.bytes != database.len()
+        || file.positions.values().any(|position| *position >= file.spans.len())
+        || file.spans.iter().any(|(start, length)| start + *length as u64 > file.bytes) {
+            return None;
+        }
+        Some(Index { cards: Cards::File(file.spans), positions: file.positions })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::oracle;
+    use crate::types::{AbilityKind, Keys, Keywords};
+
+    #[test]
+    fn inflected_words_match() {
+        assert!(oracle::contains("Whenever CARDNAME becomes tapped, draw a card.", "tap"));
+        assert!(oracle::contains("Sacrificing a creature", "sacrifice"));
+        assert!(!oracle::contains("Put ten counters on it.", "1"));
+    }
+    #[test]
+    fn non_ascii_text_does_not_split_characters() {
+        let text = "Andúril, Flame of the West gives équipped creatures +3/+1 in Lothlórien.";
+
+        assert!(!oracle::contains(text, "and"));
+        assert!(!oracle::contains(text, "lothl"));
+        assert!(oracle::contains(text, "andúril"));
+        assert!(oracle::contains(text, "creature"));
+        assert_eq!(oracle::abilities(text, false).len(), 1);
+    }
+    #[test]
+    fn whole_words_only() {
+        assert!(oracle::contains("Search your library for a land card.", "Land"));
+        assert!(!oracle::contains("Island", "Land"));
+        assert!(!oracle::contains("Islandwalk", "island"));
+        assert!(oracle::contains("Target player draws two cards.", "draw two card"));
+        assert!(!oracle::matches("Target player draws two cards.", "draw two card", false));
+        assert!(oracle::matches("Whenever you cast a spell, draw a card.", "cast a spell", false));
+        assert!(!oracle::contains("Draw a card.", "draw two"));
+        assert!(oracle::contains("any text", ""));
+    }
+    #[test]
+    fn cardname_templates() {
+        assert_eq!(oracle::templated("Tap Kiki-Jiki: Create a token.", "Kiki-Jiki, Mirror Breaker"), "Tap CARDNAME: Create a token.");
+        assert_eq!(oracle::templated("Sacrifice Thalia, Guardian of Thraben: Draw.", "Thalia, Guardian of Thraben"), "Sacrifice CARDNAME: Draw.");
+        assert_eq!(oracle::templated("Fire deals 2 damage. Ice taps a permanent.", "Fire // Ice"), "CARDNAME deals 2 damage. CARDNAME taps a permanent.");
+        // The short name "Ice" is no part of "Icebreaker" or "Spice"
+        assert_eq!(oracle::templated("Icebreaker and Spice stay, Ice goes.", "Fire // Ice"), "Icebreaker and Spice stay, CARDNAME goes.");
+        assert_eq!(oracle::templated("Kiki-Jiki's ability", "Kiki-Jiki, Mirror Breaker"), "CARDNAME's ability");
+    }
+    #[test]
+    fn hyphens_split_words() {
+        assert_eq!(oracle::tokenize("Kiki-Jiki's non-Human"), vec!["Kiki", "-", "Jiki's", "non", "-", "Human"]);
+        assert_eq!(oracle::tokenize("{T}, {2/W}: Add {G}."), vec!["{T}", ",", "{2/W}", ":", "Add", "{G}", "."]);
+    }
+    #[test]
+    fn ability_kinds() {
+        let kinds = |text: &str, spell: bool| oracle::abilities(text, spell).iter().map(|ability| ability.kind).collect::<Vec<AbilityKind>>();
+
+        let abilities = oracle::abilities("{T}, Sacrifice CARDNAME: Add one mana of any color.", false);
+        assert_eq!(abilities[0].kind, AbilityKind::Activated);
+        assert_eq!(abilities[0].cost.as_deref(), Some("{T}, Sacrifice CARDNAME"));
+        assert!(abilities[0].keys.contains(&Keys::Add));
+
+        assert_eq!(kinds("+1: Draw a card.\n\u{2212}3: Destroy target creature.", false), vec![AbilityKind::Activated, AbilityKind::Activated]);
+        assert_eq!(kinds("Whenever a creature dies, target player loses 1 life.", false), vec![AbilityKind::Triggered]);
+        assert_eq!(kinds("At the beginning of your upkeep, scry 1.", false), vec![AbilityKind::Triggered]);
+        assert_eq!(kinds("If you would draw a card, exile the top card instead.", false), vec![AbilityKind::Replacement]);
+        assert_eq!(kinds("CARDNAME enters tapped.", false), vec![AbilityKind::Replacement]);
+        assert_eq!(kinds("Creatures you control have hexproof.", false), vec![AbilityKind::Static]);
+        assert_eq!(kinds("Draw two cards.", true), vec![AbilityKind::Spell]);
+        // A colon late in a sentence is no cost
+        assert_eq!(kinds("Return target creature card from your graveyard to your hand. It gains the following ability until end of turn: Flying.", false), vec![AbilityKind::Static]);
+        // Reminder text is no ability of its own
+        assert_eq!(kinds("Flying (This creature can't be blocked except by creatures with flying or reach.)", false), vec![AbilityKind::Static]);
+    }
+    #[test]
+    fn ability_flags_need_whole_words() {
+        let ability = &oracle::abilities("Scryfall lists additional printings.", false)[0];
+        assert!(!ability.keys.contains(&Keys::Scry));
+        assert!(!ability.keywords.contains(&Keywords::Scry));
+        assert!(!ability.keys.contains(&Keys::Add));
+
+        let ability = &oracle::abilities("Scry 2, then add {C}.", false)[0];
+        assert!(ability.keys.contains(&Keys::Scry));
+        assert!(ability.keys.contains(&Keys::Add));
+    }
+}
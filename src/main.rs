@@ -1,13 +1,22 @@
 use std::{fs, io};
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg, SubCommand};
 use crate::{types::{Deck, CEResult}, logic::{database}, statistic::tutor};
 use crate::statistic::basic;
 use crate::statistic::archetype;
+use crate::statistic::probability::Play;
+use crate::statistic::powerlevel::Powerlevel;
+use crate::statistic::goldfish;
+use crate::report::Report;
+use crate::query::Query;
 
 mod types;
+mod config;
 mod import;
 mod logic;
 mod statistic;
+mod report;
+mod query;
+mod rules;
 
 pub fn check_database(offline: bool, verbose: bool) {
      
@@ -21,7 +30,9 @@ pub fn check_database(offline: bool, verbose: bool) {
     } else {
         println_verbose!(verbose, "Offline - Mode active, checking on data correct and existing.");
         logic::database::load().expect("Can not load databases, fatal in offline modus");
-        
+        if let Err(e) = import::combo::update_local() {
+            println!("{}", e);
+        }
     }
 }
 pub fn check_deck (offline: bool, verbose: bool, input: String) -> CEResult<Deck> {
@@ -51,7 +62,7 @@ pub fn load_register(offline: bool, verbose: bool, input: String) -> CEResult<Ve
 
     for path in entries {
         println_verbose!(verbose, "Queue: {:?}", &path);
-        decklists.push(path.into_os_string().into_string().expect("Path not UFT8 formated").replace(&input, "")); 
+        decklists.push(path.into_os_string().into_string().expect("Path not UFT8 formated")); 
     }
 
     for decklist in decklists {
@@ -67,19 +78,153 @@ pub fn load_register(offline: bool, verbose: bool, input: String) -> CEResult<Ve
 }
 
 fn main() {
-    let args = get_app().get_matches();
+    let app = get_app().get_matches();
+    // Global options are read from the subcommand when there is one, clap copies them down
+    let (subcommand, args) = match app.subcommand() {
+        (name, Some(t)) => (name, t),
+        _ => ("", &app),
+    };
     
     let input = args.value_of("input").unwrap_or("Error");
     let verbose = args.is_present("verbose");
     let register = args.is_present("register");
     let offline = args.is_present("offline");
+    let format = args.value_of("format").unwrap_or("text");
+    let output = args.value_of("output");
+    // Text reports are printed while they are built, only json can go to a file
+    if output.is_some() && format != "json" {
+        println!("Error: --output needs --format json");
+        return;
+    }
+    let export = args.value_of("export");
+    let query = match args.value_of("query").map(Query::parse) {
+        Some(Ok(t)) => Some(t),
+        Some(Err(e)) => {
+            println!("Error: {}", e);
+            return;
+        },
+        None => None,
+    };
+    let play = if args.is_present("draw") { Play::OnTheDraw } else { Play::OnThePlay };
     println_verbose!(verbose, "Verbose is active");
 
+    let settings = config::Config::new(args.value_of("data-dir"))
+        .scryfall(args.value_of("scryfall-url"))
+        .fixtures(args.value_of("fixtures"))
+        .combos(args.value_of("combos"))
+        .rules(args.value_of("rules"));
+
+    let settings = match config::init(settings) {
+        Ok(t) => {
+            println_verbose!(verbose, "Data directory: {}", t.data_dir.display());
+            t
+        },
+        Err(e) => {
+            println!("Error: {}", e);
+            return;
+        },
+    };
+
+    match rules::init(settings.rules_file().as_deref()) {
+        Ok(_) => println_verbose!(verbose, "Classification rules: {}", settings.rules_file().map(|t| t.display().to_string()).unwrap_or_else(|| "default".to_string())),
+        Err(e) => {
+            println!("Error: {}", e);
+            return;
+        },
+    }
+
     // update routine to load or check neccessary data
     check_database(offline, verbose);
     
 
-    if register {
+    if subcommand == "goldfish" {
+        // Checked by the validators, clap reports invalid values before we get here
+        let games = args.value_of("games").unwrap_or("10000").parse::<usize>().unwrap_or(10000);
+        let turns = args.value_of("turns").unwrap_or("6").parse::<u8>().unwrap_or(6);
+        let seed = args.value_of("seed").and_then(|t| t.parse::<u64>().ok());
+
+        let rule = match goldfish::KeepRule::parse(args.value_of("keep").unwrap_or("2-5")) {
+            Ok(t) => t,
+            Err(e) => {
+                println!("Error: {}", e);
+                return;
+            },
+        };
+
+        match check_deck(offline, verbose, input.to_string()) {
+            Ok(t) => {
+                let basics = match basic::Basic::new(&t) {
+                    Ok(basics) => basics,
+                    Err(e) => {
+                        println!("Error: {}", e);
+                        return;
+                    },
+                };
+                let roles = goldfish::Roles::new(&basics).tutors(&tutor::tutor(&t));
+
+                match goldfish::simulate(&t, &roles, games, turns, play, &rule, seed) {
+                    Ok(result) => write(format, output, &result.to_json(), || result.println()),
+                    Err(e) => println!("Error: {}", e),
+                }
+                Deck::save(&t);
+            },
+            Err(e) => println!("Error: {}", e),
+        }
+    } else if subcommand == "mulligan" {
+        // Checked by the validators like the goldfish options
+        let hands = args.value_of("hands").unwrap_or("10000").parse::<usize>().unwrap_or(10000);
+        let examples = args.value_of("examples").unwrap_or("3").parse::<usize>().unwrap_or(3);
+        let seed = args.value_of("seed").and_then(|t| t.parse::<u64>().ok());
+
+        let mut rules = Vec::<goldfish::KeepRule>::new();
+        for rule in args.values_of("rule").map(|t| t.collect()).unwrap_or_else(|| vec!["2-5"]) {
+            match goldfish::KeepRule::parse(rule) {
+                Ok(t) => rules.push(t),
+                Err(e) => {
+                    println!("Error: {}", e);
+                    return;
+                },
+            }
+        }
+
+        match check_deck(offline, verbose, input.to_string()) {
+            Ok(t) => {
+                let basics = match basic::Basic::new(&t) {
+                    Ok(basics) => basics,
+                    Err(e) => {
+                        println!("Error: {}", e);
+                        return;
+                    },
+                };
+                let roles = goldfish::Roles::new(&basics).tutors(&tutor::tutor(&t));
+
+                match goldfish::evaluate(&t, &roles, &rules, hands, examples, seed) {
+                    Ok(result) => {
+                        let json = serde_json::to_string_pretty(&result).expect("Mulligan results are always serializable");
+                        write(format, output, &json, || for evaluation in &result { evaluation.println() });
+                    },
+                    Err(e) => println!("Error: {}", e),
+                }
+                Deck::save(&t);
+            },
+            Err(e) => println!("Error: {}", e),
+        }
+    } else if let Some(query) = query {
+        match check_deck(offline, verbose, input.to_string()) {
+            Ok(t) => {
+                let cards: Vec<&str> = query.filter(&t).iter().map(|card| card.name.as_str()).collect();
+                let json = serde_json::to_string_pretty(&cards).expect("Card names are always serializable");
+
+                write(format, output, &json, || {
+                    println!("Cards matching {}({}):", args.value_of("query").unwrap_or_default(), cards.len());
+                    for card in &cards {
+                        println!("{}", card);
+                    }
+                });
+            },
+            Err(e) => println!("Error: {}", e),
+        }
+    } else if register {
         match load_register(offline, verbose, input.to_string()) {
             Ok(t) => {
                 for deck in &t {
@@ -92,39 +237,27 @@ fn main() {
          // passing check_deck, struct Deck is complete and correct
         match check_deck( offline, verbose, input.to_string()) {
             Ok(t) => {
-                println!("Deck name: {}", t.name);
-                let basics = basic::Basic::new(&t);
+                let basics = match basic::Basic::new(&t) {
+                    Ok(basics) => basics,
+                    Err(e) => {
+                        println!("Error: {}", e);
+                        return;
+                    },
+                };
                 let tutors = tutor::tutor(&t);
-                basics.println();
+                let powerlevel = Powerlevel::new(&basics, &tutors);
+                let consistency = archetype::from(&t, &basics, &tutors);
 
+                let report = Report::new(&t, &basics, &tutors, &consistency, &powerlevel, play);
 
-        
-                println!("\n Tutorlinking: \n"); 
-                for (tutor,link) in &tutors.tutor {
-                    println!("\n Targets for {} : \n", &tutor);
-                    for card in link {
-                        println!("{}",card.name);
-                    }
-                }
-                println!("\n Fetches: \n");
-                for (fetch, link) in &tutors.fetches {
-                    println!("\n Targets for {}: \n", &fetch);
-                    for card in link {
-                        println!("{}", card.name);
-                    }
-                }
-                println!("\n Landramp: \n");
-                for (ramp, link) in &tutors.landramp {
-                    println!("\n Targets for {}: \n", &ramp);
-                    for card in link {
-                        println!("{}", card.name);
+                write(format, output, &report.to_json(), || report.println());
+
+                if let Some(path) = export {
+                    match t.export_dek(path) {
+                        Ok(_) => println!("Exported {} to {}", t.name, path),
+                        Err(e) => println!("Error: {}", e),
                     }
                 }
-                
-
-                let consistency = archetype::from(&t, &basics,  tutors); 
-                
-                consistency.println();
 
                 Deck::save(&t);  
             },
@@ -134,16 +267,37 @@ fn main() {
    
     
 }
+// Json goes to the output file or stdout, text is printed
+fn write(format: &str, output: Option<&str>, json: &str, text: impl Fn()) {
+    if format == "json" {
+        match output {
+            Some(path) => if let Err(e) = fs::write(path, json) {
+                println!("Error: Can not write report {}: {}", path, e);
+            },
+            None => println!("{}", json),
+        }
+    } else {
+        text();
+    }
+}
 
+// Rejects values the subcommands could not use instead of falling back to the default
+fn number<T: std::str::FromStr>(value: String) -> Result<(), String> {
+    match value.parse::<T>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(format!("'{}' is not a valid number", value)),
+    }
+}
 fn get_app() -> App<'static, 'static>{
     App::new("mtg analyizer")
     .version("0.1")
     .author("Maximilian Wittich <maxi.wittich@outlook.com>")
-    .about("Reads moxfield mtgo export lists to analyze the deck. Please add *CMDR* in the line your commander is")
+    .about("Reads Arena, MTGO and moxfield export lists to analyze the deck. Plain lists need *CMDR* in the line your commander is")
+    .setting(AppSettings::SubcommandsNegateReqs)
     .arg(
         Arg::with_name("input")
         .required(true)
-        .help("Path to decklist or folder")
+        .help("Path to decklist or folder, plain names are looked up in the decks folder of the data directory")
         .index(1)
     )
     .arg(
@@ -156,14 +310,170 @@ fn get_app() -> App<'static, 'static>{
         Arg::with_name("verbose")
         .short("v")
         .long("verbose")
+        .global(true)
         .help("If set, verbose mode is activated")
     )
     .arg(
         Arg::with_name("offline")
         .short("o")
         .long("offline")
+        .global(true)
         .help("Only uses database for card import")
     )
-
-
+    .arg(
+        Arg::with_name("draw")
+        .short("d")
+        .long("draw")
+        .global(true)
+        .help("Calculates draw probabilities on the draw instead of on the play")
+    )
+    .arg(
+        Arg::with_name("format")
+        .short("f")
+        .long("format")
+        .takes_value(true)
+        .global(true)
+        .possible_values(&["text", "json"])
+        .default_value("text")
+        .help("Output format of the analysis report")
+    )
+    .arg(
+        Arg::with_name("output")
+        .long("output")
+        .takes_value(true)
+        .global(true)
+        .help("Writes the json report to a file instead of stdout, needs --format json")
+    )
+    .arg(
+        Arg::with_name("export")
+        .long("export")
+        .takes_value(true)
+        .help("Writes the deck as MTGO .dek file to the given path")
+    )
+    .arg(
+        Arg::with_name("query")
+        .short("q")
+        .long("query")
+        .takes_value(true)
+        .help("Lists the cards of the deck matching a query like 'keyword:flying & cmc<=3 & !type:land'")
+    )
+    .arg(
+        Arg::with_name("data-dir")
+        .long("data-dir")
+        .takes_value(true)
+        .global(true)
+        .help("Directory for card database, combos, saved decks and decklists. Defaults to $MTG_ANALYZER_DATA or $XDG_DATA_HOME/mtg-analyzer")
+    )
+    .arg(
+        Arg::with_name("scryfall-url")
+        .long("scryfall-url")
+        .takes_value(true)
+        .global(true)
+        .help("Base url of the scryfall api. Defaults to $MTG_ANALYZER_SCRYFALL or https://api.scryfall.com")
+    )
+    .arg(
+        Arg::with_name("fixtures")
+        .long("fixtures")
+        .takes_value(true)
+        .global(true)
+        .help("Reads card json from a folder instead of scryfall")
+    )
+    .arg(
+        Arg::with_name("combos")
+        .long("combos")
+        .takes_value(true)
+        .global(true)
+        .help("Imports combos from a Commander Spellbook json export instead of the combo sheet. Defaults to $MTG_ANALYZER_COMBOS")
+    )
+    .arg(
+        Arg::with_name("rules")
+        .long("rules")
+        .takes_value(true)
+        .global(true)
+        .help("Json file with classification rules per effect category. Defaults to $MTG_ANALYZER_RULES or rules.json in the data directory")
+    )
+    .subcommand(
+        SubCommand::with_name("goldfish")
+        .about("Plays the opening turns of many shuffled games and reports commander turn, mana and hand size")
+        .arg(
+            Arg::with_name("input")
+            .required(true)
+            .help("Path to decklist")
+            .index(1)
+        )
+        .arg(
+            Arg::with_name("games")
+            .short("g")
+            .long("games")
+            .takes_value(true)
+            .validator(number::<usize>)
+            .default_value("10000")
+            .help("Number of simulated games")
+        )
+        .arg(
+            Arg::with_name("turns")
+            .short("t")
+            .long("turns")
+            .takes_value(true)
+            .validator(number::<u8>)
+            .default_value("6")
+            .help("Number of turns per game")
+        )
+        .arg(
+            Arg::with_name("keep")
+            .short("k")
+            .long("keep")
+            .takes_value(true)
+            .default_value("2-5")
+            .help("Mulligan rule: min-max lands, never to keep every seven, or terms like lands=2-5,ramp,tutor")
+        )
+        .arg(
+            Arg::with_name("seed")
+            .long("seed")
+            .takes_value(true)
+            .validator(number::<u64>)
+            .help("Seed for reproducible shuffles")
+        )
+    )
+    .subcommand(
+        SubCommand::with_name("mulligan")
+        .about("Samples opening hands and compares how often mulligan rules keep them")
+        .arg(
+            Arg::with_name("input")
+            .required(true)
+            .help("Path to decklist")
+            .index(1)
+        )
+        .arg(
+            Arg::with_name("rule")
+            .long("rule")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Keep rule like lands=2-5,ramp,tutor=1,mulligans=2, repeat to compare several rules [default: 2-5]")
+        )
+        .arg(
+            Arg::with_name("hands")
+            .long("hands")
+            .takes_value(true)
+            .validator(number::<usize>)
+            .default_value("10000")
+            .help("Number of sampled opening hands")
+        )
+        .arg(
+            Arg::with_name("examples")
+            .long("examples")
+            .takes_value(true)
+            .validator(number::<usize>)
+            .default_value("3")
+            .help("Example hands shown per rule for kept and mulliganed sevens")
+        )
+        .arg(
+            Arg::with_name("seed")
+            .long("seed")
+            .takes_value(true)
+            .validator(number::<u64>)
+            .help("Seed for reproducible shuffles")
+        )
+    )
 }
@@ -0,0 +1,453 @@
+/********************************** Card Queries ***************************************/
+
+use std::{fmt, ops::{BitAnd, BitOr, Not}};
+use strum::IntoEnumIterator;
+use regex::Regex;
+use crate::types::*;
+use crate::logic;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compare {
+    Less,
+    LessEqual,
+    Equal,
+    GreaterEqual,
+    Greater,
+}
+impl Compare {
+    fn test(&self, value: f32, bound: f32) -> bool {
+        match self {
+            Compare::Less => value < bound,
+            Compare::LessEqual => value <= bound,
+            Compare::Equal => value == bound,
+            Compare::GreaterEqual => value >= bound,
+            Compare::Greater => value > bound,
+        }
+    }
+}
+
+// Regex has no equality, patterns compare by their source
+#[derive(Debug, Clone)]
+pub struct Pattern(Regex);
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+/* Typed predicate over a card, combined with & | and !
+   Query::keyword(Keywords::Flying) & Query::cmc(Compare::LessEqual, 3.0) & !Query::cardtype(CardType::Land(None)) */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    Any,
+    // Exact card name, case insensitive
+    Name(String),
+    // Type line, subtypes are only checked when the query has some
+    CardType(CardType),
+    // Types the oracle text refers to
+    OracleType(CardType),
+    Key(Keys),
+    Zone(Zones),
+    Keyword(Keywords),
+    Restriction(Restrictions),
+    Cmc(Compare, f32),
+    Power(Compare, f32),
+    Toughness(Compare, f32),
+    Loyality(Compare, f32),
+    Legendary,
+    Commander,
+    // Oracle text in one of the CardFields::Oracle* modes
+    Text(CardFields, String),
+    // Case insensitive regular expression on the oracle text, compiled once for every card
+    Regex(Pattern),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+}
+
+impl Query {
+    pub fn name(name: &str) -> Query {
+        Query::Name(name.to_string())
+    }
+    pub fn cardtype(cardtype: CardType) -> Query {
+        Query::CardType(cardtype)
+    }
+    pub fn oracle_type(cardtype: CardType) -> Query {
+        Query::OracleType(cardtype)
+    }
+    pub fn key(key: Keys) -> Query {
+        Query::Key(key)
+    }
+    pub fn zone(zone: Zones) -> Query {
+        Query::Zone(zone)
+    }
+    pub fn keyword(keyword: Keywords) -> Query {
+        Query::Keyword(keyword)
+    }
+    pub fn restriction(restriction: Restrictions) -> Query {
+        Query::Restriction(restriction)
+    }
+    pub fn cmc(compare: Compare, value: f32) -> Query {
+        Query::Cmc(compare, value)
+    }
+    pub fn cmc_le(value: f32) -> Query {
+        Query::Cmc(Compare::LessEqual, value)
+    }
+    pub fn cmc_lt(value: f32) -> Query {
+        Query::Cmc(Compare::Less, value)
+    }
+    pub fn phrase(text: &str) -> Query {
+        Query::Text(CardFields::OraclePhrase, text.to_string())
+    }
+    pub fn word(text: &str) -> Query {
+        Query::Text(CardFields::OracleWord, text.to_string())
+    }
+    pub fn regex(pattern: &str) -> CEResult<Query> {
+        match Regex::new(&format!("(?i){}", pattern)) {
+            Ok(t) => Ok(Query::Regex(Pattern(t))),
+            Err(e) => Err(CEerror::QueryError(format!("Invalid regex {}: {}", pattern, e))),
+        }
+    }
+    // The card mentions itself, "Sacrifice CARDNAME"
+    pub fn refers_to_self() -> Query {
+        Query::Text(CardFields::OracleTemplate, "CARDNAME".to_string())
+    }
+    // Any of the queries, false for none
+    pub fn any_of(queries: Vec<Query>) -> Query {
+        queries.into_iter().reduce(|a, b| a | b).unwrap_or_else(|| !Query::Any)
+    }
+    pub fn matches(&self, card: &Card) -> bool {
+        match self {
+            Query::Any => true,
+            Query::Name(name) => card.name.to_lowercase() == name.to_lowercase(),
+            Query::CardType(cardtype) => card.cardtype.iter().any(|t| same_type(t, cardtype)),
+            Query::OracleType(cardtype) => card.oracle_types.iter().flatten().any(|t| same_type(t, cardtype)),
+            Query::Key(key) => card.keys.iter().flatten().any(|t| t == key),
+            Query::Zone(zone) => card.zones.iter().flatten().any(|t| t == zone),
+            Query::Keyword(keyword) => card.keywords.iter().flatten().any(|t| t == keyword),
+            Query::Restriction(restriction) => card.restrictions.iter().flatten().any(|t| t == restriction),
+            Query::Cmc(compare, value) => compare.test(card.cmc, *value),
+            Query::Power(compare, value) => card.stats.iter().flatten().any(|t| matches!(t, Stats::Power(p) if compare.test(*p as f32, *value))),
+            Query::Toughness(compare, value) => card.stats.iter().flatten().any(|t| matches!(t, Stats::Toughness(p) if compare.test(*p as f32, *value))),
+            Query::Loyality(compare, value) => card.stats.iter().flatten().any(|t| matches!(t, Stats::Loyality(p) if compare.test(*p as f32, *value))),
+            Query::Legendary => card.legendary,
+            Query::Commander => card.commander,
+            Query::Text(field, text) => card.contains(text, field.clone()),
+            Query::Regex(pattern) => pattern.0.is_match(&logic::oracle::unescape(&card.oracle_text)),
+            Query::And(a, b) => a.matches(card) && b.matches(card),
+            Query::Or(a, b) => a.matches(card) || b.matches(card),
+            Query::Not(a) => !a.matches(card),
+        }
+    }
+    pub fn filter<'deck>(&self, deck: &'deck Deck) -> Vec<&'deck Card> {
+        deck.commander.iter().chain(deck.companion.iter()).chain(deck.library.iter())
+            .filter(|card| self.matches(card))
+            .collect()
+    }
+    /* Command line syntax, & binds stronger than |, ! negates and parentheses group
+         keyword:flying & cmc<=3 & !type:land
+         (key:draw | text:"look at the top") & zone:hand
+       Fields: name type otype key zone keyword restriction text word regex, cmc power toughness loyality
+       with < <= = >= >, and the flags legendary commander self */
+    pub fn parse(input: &str) -> CEResult<Query> {
+        let tokens = lex(input)?;
+        let mut parser = Parser { tokens, position: 0 };
+        let query = parser.or()?;
+
+        match parser.peek() {
+            None => Ok(query),
+            Some(t) => Err(CEerror::QueryError(format!("Unexpected {} in {}", t, input))),
+        }
+    }
+}
+
+impl BitAnd for Query {
+    type Output = Query;
+    fn bitand(self, other: Query) -> Query {
+        Query::And(Box::new(self), Box::new(other))
+    }
+}
+impl BitOr for Query {
+    type Output = Query;
+    fn bitor(self, other: Query) -> Query {
+        Query::Or(Box::new(self), Box::new(other))
+    }
+}
+impl Not for Query {
+    type Output = Query;
+    fn not(self) -> Query {
+        Query::Not(Box::new(self))
+    }
+}
+
+// Creature(None) is any creature, Creature(Some(subtypes)) needs all of them
+fn same_type(card: &CardType, query: &CardType) -> bool {
+    if std::mem::discriminant(card) != std::mem::discriminant(query) {
+        return false;
+    }
+    fn subtypes<T: PartialEq>(card: &Option<Vec<T>>, query: &Option<Vec<T>>) -> bool {
+        match query {
+            None => true,
+            Some(wanted) => wanted.iter().all(|t| card.iter().flatten().any(|c| c == t)),
+        }
+    }
+    match (card, query) {
+        (CardType::Instant(c), CardType::Instant(q)) => subtypes(c, q),
+        (CardType::Sorcery(c), CardType::Sorcery(q)) => subtypes(c, q),
+        (CardType::Artifact(c), CardType::Artifact(q)) => subtypes(c, q),
+        (CardType::Creature(c), CardType::Creature(q)) => subtypes(c, q),
+        (CardType::Enchantment(c), CardType::Enchantment(q)) => subtypes(c, q),
+        (CardType::Land(c), CardType::Land(q)) => subtypes(c, q),
+        // Types without subtypes
+        _ => card == query,
+    }
+}
+
+/*********************************** Parser *******************************************/
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    Compare(Compare),
+    Colon,
+    Word(String),
+}
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(t) => write!(f, "\"{}\"", t),
+            Token::Compare(t) => write!(f, "{:?}", t),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+fn lex(input: &str) -> CEResult<Vec<Token>> {
+    let mut tokens = Vec::<Token>::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => tokens.push(Token::And),
+            '|' => tokens.push(Token::Or),
+            '!' => tokens.push(Token::Not),
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            ':' => tokens.push(Token::Colon),
+            '=' => tokens.push(Token::Compare(Compare::Equal)),
+            '<' | '>' => {
+                let equal = chars.peek() == Some(&'=');
+                if equal {
+                    chars.next();
+                }
+                tokens.push(Token::Compare(match (c, equal) {
+                    ('<', false) => Compare::Less,
+                    ('<', true) => Compare::LessEqual,
+                    ('>', false) => Compare::Greater,
+                    _ => Compare::GreaterEqual,
+                }));
+            },
+            '"' => {
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(t) => word.push(t),
+                        None => return Err(CEerror::QueryError(format!("Missing closing quote in {}", input))),
+                    }
+                }
+                tokens.push(Token::Word(word));
+            },
+            _ if c.is_whitespace() => (),
+            _ => {
+                let mut word = String::from(c);
+                while let Some(next) = chars.peek() {
+                    if next.is_whitespace() || "&|!():=<>\"".contains(*next) {
+                        break;
+                    }
+                    word.push(*next);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            },
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+    fn or(&mut self) -> CEResult<Query> {
+        let mut query = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            query = query | self.and()?;
+        }
+        Ok(query)
+    }
+    fn and(&mut self) -> CEResult<Query> {
+        let mut query = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            query = query & self.unary()?;
+        }
+        Ok(query)
+    }
+    fn unary(&mut self) -> CEResult<Query> {
+        match self.next() {
+            Some(Token::Not) => Ok(!self.unary()?),
+            Some(Token::Open) => {
+                let query = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(query),
+                    _ => Err(CEerror::QueryError("Missing closing parenthesis".to_string())),
+                }
+            },
+            Some(Token::Word(field)) => self.term(&field.to_lowercase()),
+            Some(t) => Err(CEerror::QueryError(format!("Expected a field, found {}", t))),
+            None => Err(CEerror::QueryError("Query ends too early".to_string())),
+        }
+    }
+    fn term(&mut self, field: &str) -> CEResult<Query> {
+        match field {
+            "legendary" => return Ok(Query::Legendary),
+            "commander" => return Ok(Query::Commander),
+            "self" => return Ok(Query::refers_to_self()),
+            _ => (),
+        }
+        let compare = match self.next() {
+            Some(Token::Colon) => None,
+            Some(Token::Compare(t)) => Some(t),
+            _ => return Err(CEerror::QueryError(format!("Expected : or a comparison after {}", field))),
+        };
+        let value = match self.next() {
+            Some(Token::Word(t)) => t,
+            _ => return Err(CEerror::QueryError(format!("Expected a value after {}", field))),
+        };
+
+        // Numbers compare with : as =
+        if let "cmc" | "mv" | "power" | "toughness" | "loyality" | "loyalty" = field {
+            let compare = compare.unwrap_or(Compare::Equal);
+            let number = value.parse::<f32>().map_err(|_| CEerror::QueryError(format!("{} is not a number", value)))?;
+            return match field {
+                "cmc" | "mv" => Ok(Query::cmc(compare, number)),
+                "power" => Ok(Query::Power(compare, number)),
+                "toughness" => Ok(Query::Toughness(compare, number)),
+                _ => Ok(Query::Loyality(compare, number)),
+            };
+        }
+        if compare.is_some() {
+            return Err(CEerror::QueryError(format!("{} can not be compared", field)));
+        }
+        match field {
+            "name" => Ok(Query::name(&value)),
+            "type" => Ok(Query::cardtype(lookup(CardType::iter(), &value)?)),
+            "otype" => Ok(Query::oracle_type(lookup(CardType::iter(), &value)?)),
+            "key" => Ok(Query::key(lookup(Keys::iter(), &value)?)),
+            "zone" => Ok(Query::zone(lookup(Zones::iter(), &value)?)),
+            "keyword" => Ok(Query::keyword(lookup(Keywords::iter(), &value)?)),
+            "restriction" => Ok(Query::restriction(lookup(Restrictions::iter(), &value)?)),
+            "text" => Ok(Query::phrase(&value)),
+            "word" => Ok(Query::word(&value)),
+            "regex" => Query::regex(&value),
+            _ => Err(CEerror::QueryError(format!("Unknown field {}", field))),
+        }
+    }
+}
+
+// Enum value by its printed or variant name, "first strike", "first_strike" and "FirstStrike" all work
+fn lookup<T: fmt::Display + fmt::Debug>(values: impl Iterator<Item = T>, input: &str) -> CEResult<T> {
+    let simple = |t: &str| t.to_lowercase().replace(['_', ' ', '-'], "");
+    let wanted = simple(input);
+
+    for value in values {
+        let debug = format!("{:?}", value);
+        let debug = debug.split('(').next().unwrap_or_default();
+        if simple(&value.to_string()) == wanted || simple(debug) == wanted {
+            return Ok(value);
+        }
+    }
+    Err(CEerror::QueryError(format!("Unknown value {}", input)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+    use super::{Compare, Query};
+    use crate::types::{Card, CardType, CreatureSubtype, Keys, Keywords, Zones};
+
+    fn fixture(name: &str) -> Card {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(format!("{}.json", name));
+        Card::make(&fs::read_to_string(path).unwrap(), false).unwrap()
+    }
+
+    #[test]
+    fn documented_examples() {
+        assert_eq!(Query::parse("keyword:flying & cmc<=3 & !type:land").unwrap(),
+            Query::keyword(Keywords::Flying) & Query::cmc_le(3.0) & !Query::cardtype(CardType::Land(None)));
+        assert_eq!(Query::parse(r#"(key:draw | text:"look at the top") & zone:hand"#).unwrap(),
+            (Query::key(Keys::Draw) | Query::phrase("look at the top")) & Query::zone(Zones::Hand));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let a = || Query::name("a");
+        let b = || Query::name("b");
+        let c = || Query::name("c");
+
+        assert_eq!(Query::parse("name:a | name:b & name:c").unwrap(), a() | (b() & c()));
+        assert_eq!(Query::parse("name:a & name:b | name:c").unwrap(), (a() & b()) | c());
+        assert_eq!(Query::parse("(name:a | name:b) & name:c").unwrap(), (a() | b()) & c());
+    }
+
+    #[test]
+    fn negation() {
+        assert_eq!(Query::parse("!legendary").unwrap(), !Query::Legendary);
+        assert_eq!(Query::parse("!!legendary").unwrap(), !!Query::Legendary);
+        assert_eq!(Query::parse("!(legendary | commander)").unwrap(), !(Query::Legendary | Query::Commander));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert!(Query::parse(r#"text:"look at the top"#).is_err());
+        assert!(Query::parse("(legendary | commander").is_err());
+        assert!(Query::parse("legendary)").is_err());
+        assert!(Query::parse("legendary &").is_err());
+        assert!(Query::parse("name:").is_err());
+        assert!(Query::parse("cmc:three").is_err());
+        assert!(Query::parse("name<3").is_err());
+        assert!(Query::parse("colour:red").is_err());
+    }
+
+    #[test]
+    fn numbers_compare_with_colon_as_equal() {
+        assert_eq!(Query::parse("cmc:3").unwrap(), Query::parse("cmc=3").unwrap());
+        assert_eq!(Query::parse("power:2").unwrap(), Query::Power(Compare::Equal, 2.0));
+        assert_eq!(Query::parse("mv<2").unwrap(), Query::cmc_lt(2.0));
+    }
+
+    #[test]
+    fn types_ignore_subtypes_unless_asked() {
+        let elves = fixture("llanowar-elves");
+
+        assert!(Query::parse("type:creature").unwrap().matches(&elves));
+        assert!(!Query::parse("type:land").unwrap().matches(&elves));
+        assert!(Query::cardtype(CardType::Creature(Some(vec![CreatureSubtype::Elf]))).matches(&elves));
+        assert!(!Query::cardtype(CardType::Creature(Some(vec![CreatureSubtype::Goblin]))).matches(&elves));
+    }
+}
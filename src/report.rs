@@ -0,0 +1,524 @@
+/********************************** Analysis Report ***************************************/
+
+use std::collections::BTreeMap;
+use serde::Serialize;
+
+use crate::types::{Card, Deck};
+use crate::statistic::{basic::Basic, tutor::Tutor, archetype::Consistency, powerlevel::Powerlevel};
+use crate::statistic::probability::{self, Play};
+use crate::statistic::manabase::{self, Manabase};
+use crate::statistic::goldfish::{self, Goldfish, Evaluation, Distribution};
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub name: String,
+    pub commander: Vec<String>,
+    pub color_identity: Vec<String>,
+    pub violations: Vec<String>,
+    pub cardtype: CardtypeReport,
+    pub mana_cost: BTreeMap<u8, usize>,
+    pub mana_dist: ManaDistReport,
+    pub effect: EffectReport,
+    pub probabilities: ProbabilityReport,
+    pub tutors: TutorReport,
+    pub combos: Vec<ComboReport>,
+    pub near_misses: Vec<NearMissReport>,
+    pub foci: Vec<FocusReport>,
+    pub overlaps: Vec<u8>,
+    pub main_focus_payoff: Vec<String>,
+    pub second_focus_payoff: Vec<String>,
+    pub manabase: ManabaseReport,
+    pub powerlevel: PowerlevelReport,
+}
+#[derive(Debug, Serialize)]
+pub struct CardtypeReport {
+    pub creatures: Vec<String>,
+    pub enchantments: Vec<String>,
+    pub artifacts: Vec<String>,
+    pub lands: Vec<String>,
+    pub planeswalkers: Vec<String>,
+    pub instants: Vec<String>,
+    pub sorcerys: Vec<String>,
+}
+#[derive(Debug, Serialize)]
+pub struct ManaDistReport {
+    pub manacost: BTreeMap<String, u8>,
+    pub manaprod: BTreeMap<String, u8>,
+    pub dorks: Vec<String>,
+    pub artifacts: Vec<String>,
+    pub enchantments: Vec<String>,
+    pub lands: Vec<String>,
+}
+#[derive(Debug, Serialize)]
+pub struct EffectReport {
+    pub draw: Vec<String>,
+    pub bounce: Vec<String>,
+    pub removal: Vec<String>,
+    pub boardwipe: Vec<String>,
+    pub lord: Vec<String>,
+    pub counter: Vec<String>,
+    pub payoff: Vec<String>,
+    pub recursion: Vec<String>,
+    pub reanimation: Vec<String>,
+    pub stax: Vec<String>,
+    pub fastmana: Vec<String>,
+    pub protection: Vec<String>,
+    pub wincon: Vec<String>,
+    pub engine: Vec<String>,
+    pub tax: Vec<String>,
+}
+#[derive(Debug, Serialize)]
+pub struct ProbabilityReport {
+    pub play: String,
+    pub turns: Vec<TurnProbability>,
+}
+#[derive(Debug, Serialize)]
+pub struct TurnProbability {
+    pub turn: u8,
+    pub ramp: Option<f64>,
+    pub draw: Option<f64>,
+}
+#[derive(Debug, Serialize)]
+pub struct TutorReport {
+    pub tutor: BTreeMap<String, Vec<String>>,
+    pub fetches: BTreeMap<String, Vec<String>>,
+    pub landramp: BTreeMap<String, Vec<String>>,
+}
+#[derive(Debug, Serialize)]
+pub struct ComboReport {
+    pub id: String,
+    pub pieces: Vec<String>,
+    pub num_pieces: usize,
+    pub prerequisites: Vec<String>,
+    pub steps: Vec<String>,
+    pub results: Vec<String>,
+    pub tutors: Vec<String>,
+    pub drawn_by_turn_four: Option<f64>,
+    pub assembled: Vec<ComboTurn>,
+}
+#[derive(Debug, Serialize)]
+pub struct ComboTurn {
+    pub turn: u8,
+    pub probability: Option<f64>,
+}
+#[derive(Debug, Serialize)]
+pub struct NearMissReport {
+    pub id: String,
+    pub pieces: Vec<String>,
+    pub missing: Vec<String>,
+    pub present: usize,
+    pub results: Vec<String>,
+}
+#[derive(Debug, Serialize)]
+pub struct FocusReport {
+    pub archetype: String,
+    pub cards: Vec<String>,
+}
+#[derive(Debug, Serialize)]
+pub struct ManabaseReport {
+    pub target: f64,
+    pub lands: usize,
+    pub recommended_lands: usize,
+    pub average_mv: f64,
+    pub cheap_ramp_draw: usize,
+    pub colors: Vec<ColorSourceReport>,
+}
+#[derive(Debug, Serialize)]
+pub struct ColorSourceReport {
+    pub color: String,
+    pub pips: usize,
+    pub hardest: Option<String>,
+    pub required_sources: usize,
+    pub sources: usize,
+    pub under_supplied: bool,
+}
+#[derive(Debug, Serialize)]
+pub struct PowerlevelReport {
+    pub score: u8,
+    pub factors: Vec<FactorReport>,
+}
+#[derive(Debug, Serialize)]
+pub struct FactorReport {
+    pub name: String,
+    pub value: f32,
+    pub points: f32,
+    pub max_points: f32,
+}
+
+impl Report {
+    pub fn new(deck: &Deck, basics: &Basic, tutors: &Tutor, consistency: &Consistency, powerlevel: &Powerlevel, play: Play) -> Report {
+        let mut ramp = basics.mana_dist.dorks.clone();
+        ramp.extend(&basics.mana_dist.artifacts);
+        ramp.extend(&basics.effect.fastmana);
+
+        let mut turns = Vec::<TurnProbability>::new();
+        for turn in 1..=4 {
+            turns.push(TurnProbability {
+                turn,
+                ramp: probability::at_least(deck, &ramp, 1, turn, play).ok(),
+                draw: probability::at_least(deck, &basics.effect.draw, 1, turn, play).ok(),
+            });
+        }
+
+        let mut combos = Vec::<ComboReport>::new();
+        for (combo, tutor) in basics.combo.iter().zip(&consistency.combo_tutor) {
+            let pieces = probability::combo_pieces(deck, combo);
+            combos.push(ComboReport {
+                id: combo.combo.id.to_string(),
+                pieces: combo.pieces().to_vec(),
+                num_pieces: combo.num_pieces(),
+                prerequisites: combo.combo.prerequisites.clone(),
+                steps: combo.combo.steps.clone(),
+                results: combo.combo.results.clone(),
+                tutors: tutor.iter().map(|name| name.to_string()).collect(),
+                drawn_by_turn_four: probability::at_least(deck, &pieces, pieces.len(), 4, play).ok(),
+                assembled: (1..=6).map(|turn| ComboTurn {
+                    turn,
+                    probability: probability::assemble(deck, combo, tutors, turn, play).ok(),
+                }).collect(),
+            });
+        }
+
+        let mut near_misses = Vec::<NearMissReport>::new();
+        for combo in &basics.near_miss {
+            near_misses.push(NearMissReport {
+                id: combo.combo.id.to_string(),
+                pieces: combo.pieces().to_vec(),
+                missing: combo.missing.clone(),
+                present: combo.num_pieces() - combo.missing.len(),
+                results: combo.combo.results.clone(),
+            });
+        }
+
+        let mut foci = Vec::<FocusReport>::new();
+        for focus in &consistency.overlaps.sorted_foci {
+            foci.push(FocusReport {
+                archetype: format!("{:?}", focus.archetype),
+                cards: names(&focus.cards),
+            });
+        }
+
+        let manabase = Manabase::new(deck, basics, play, manabase::TARGET);
+        let mut colors = Vec::<ColorSourceReport>::new();
+        for advice in &manabase.colors {
+            colors.push(ColorSourceReport {
+                color: advice.color.to_string(),
+                pips: advice.pips,
+                hardest: advice.hardest.map(|card| card.name.to_string()),
+                required_sources: advice.required,
+                sources: advice.current,
+                under_supplied: advice.under_supplied(),
+            });
+        }
+
+        let mut factors = Vec::<FactorReport>::new();
+        for factor in &powerlevel.factors {
+            factors.push(FactorReport {
+                name: factor.name.to_string(),
+                value: factor.value,
+                points: factor.points,
+                max_points: factor.max_points,
+            });
+        }
+
+        Report {
+            name: deck.name.to_string(),
+            commander: deck.commander.iter().map(|card| card.name.to_string()).collect(),
+            color_identity: deck.color_identity().iter().map(|color| color.to_string()).collect(),
+            violations: deck.violations.iter().map(|violation| violation.to_string()).collect(),
+            cardtype: CardtypeReport {
+                creatures: names(&basics.cardtype.creatures),
+                enchantments: names(&basics.cardtype.enchantments),
+                artifacts: names(&basics.cardtype.artifacts),
+                lands: names(&basics.cardtype.lands),
+                planeswalkers: names(&basics.cardtype.planeswalkers),
+                instants: names(&basics.cardtype.instants),
+                sorcerys: names(&basics.cardtype.sorcerys),
+            },
+            mana_cost: basics.mana_cost.iter().map(|(cmc, cards)| (*cmc, cards.len())).collect(),
+            mana_dist: ManaDistReport {
+                manacost: basics.mana_dist.manacost.iter().map(|(color, num)| (color.to_string(), *num)).collect(),
+                manaprod: basics.mana_dist.manaprod.iter().map(|(color, num)| (color.to_string(), *num)).collect(),
+                dorks: names(&basics.mana_dist.dorks),
+                artifacts: names(&basics.mana_dist.artifacts),
+                enchantments: names(&basics.mana_dist.enchantments),
+                lands: names(&basics.mana_dist.lands),
+            },
+            effect: EffectReport {
+                draw: names(&basics.effect.draw),
+                bounce: names(&basics.effect.bounce),
+                removal: names(&basics.effect.removal),
+                boardwipe: names(&basics.effect.boardwipe),
+                lord: names(&basics.effect.lord),
+                counter: names(&basics.effect.counter),
+                payoff: names(&basics.effect.payoff),
+                recursion: names(&basics.effect.recursion),
+                reanimation: names(&basics.effect.reanimation),
+                stax: names(&basics.effect.stax),
+                fastmana: names(&basics.effect.fastmana),
+                protection: names(&basics.effect.protection),
+                wincon: names(&basics.effect.wincon),
+                engine: names(&basics.effect.engine),
+                tax: names(&basics.effect.tax),
+            },
+            probabilities: ProbabilityReport {
+                play: format!("{:?}", play),
+                turns,
+            },
+            tutors: TutorReport {
+                tutor: links(&tutors.tutor),
+                fetches: links(&tutors.fetches),
+                landramp: links(&tutors.landramp),
+            },
+            combos,
+            near_misses,
+            foci,
+            overlaps: consistency.overlaps.overlaps.clone(),
+            main_focus_payoff: names(&consistency.main_focus_payoff),
+            second_focus_payoff: names(&consistency.second_focus_payoff),
+            manabase: ManabaseReport {
+                target: manabase.target,
+                lands: manabase.lands.current,
+                recommended_lands: manabase.lands.recommended,
+                average_mv: manabase.lands.average_mv,
+                cheap_ramp_draw: manabase.lands.cheap_ramp_draw,
+                colors,
+            },
+            powerlevel: PowerlevelReport {
+                score: powerlevel.score,
+                factors,
+            },
+        }
+    }
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Report can not be formated to json")
+    }
+    pub fn println(&self) {
+        println!("Deck name: {}", self.name);
+        println!("Color identity: {}", self.color_identity.join(", "));
+
+        if !self.violations.is_empty() {
+            println!("------------------------------------------------------------");
+            println!("Commander legality violations({}):", self.violations.len());
+            for violation in &self.violations {
+                println!("{}", violation);
+            }
+            println!("------------------------------------------------------------");
+        }
+
+        println!("------------------------------------------------------------");
+        println!("Your Deck listed by types:");
+        printout("Creatures", &self.cardtype.creatures);
+        printout("Enchantments", &self.cardtype.enchantments);
+        printout("Artifacts", &self.cardtype.artifacts);
+        printout("Planeswalkers", &self.cardtype.planeswalkers);
+        printout("Instants", &self.cardtype.instants);
+        printout("Sorcerys", &self.cardtype.sorcerys);
+        printout("Lands", &self.cardtype.lands);
+        println!("------------------------------------------------------------");
+
+        println!("------------------------------------------------------------");
+        println!("Manadistribution by Pips: ");
+        for (color, num) in &self.mana_dist.manacost {
+            println!("For {} found pips: {}", color, num);
+        }
+        println!("Manaproducer by Pips: ");
+        for (color, num) in &self.mana_dist.manaprod {
+            println!("For {} found producer: {}", color, num);
+        }
+        println!("Ramp:{}", (self.mana_dist.dorks.len() + self.mana_dist.artifacts.len() + self.mana_dist.enchantments.len()));
+        println!("Distributed with Dorks({}), Artifacts({}), Land Ramp({}) and Enchantments({})",
+            self.mana_dist.dorks.len(), self.mana_dist.artifacts.len(), self.mana_dist.lands.len(), self.mana_dist.enchantments.len());
+        println!("------------------------------------------------------------");
+
+        println!("------------------------------------------------------------");
+        println!("Your Deck, seperated by effects of your 99: ");
+        printout("Your draws", &self.effect.draw);
+        printout("Bounce-Spells", &self.effect.bounce);
+        printout("Single target removal", &self.effect.removal);
+        printout("Boardwipes", &self.effect.boardwipe);
+        printout("Your buff effects", &self.effect.lord);
+        printout("Counterspells", &self.effect.counter);
+        printout("Payoffs", &self.effect.payoff);
+        printout("Recursion", &self.effect.recursion);
+        printout("Reanimator-Spells", &self.effect.reanimation);
+        printout("Stax", &self.effect.stax);
+        printout("Fast-Mana", &self.effect.fastmana);
+        printout("Protection", &self.effect.protection);
+        printout("Win conditions", &self.effect.wincon);
+        printout("Card advantage engines", &self.effect.engine);
+        printout("Tax and hatebears", &self.effect.tax);
+        println!("------------------------------------------------------------");
+
+        println!("------------------------------------------------------------");
+        println!("Manacost distribution:");
+        for (manacost, num) in &self.mana_cost {
+            println!("Manacost: {}, Number: {}", manacost, num);
+        }
+        println!("------------------------------------------------------------");
+
+        if !self.combos.is_empty() {
+            println!("------------------------------------------------------------");
+            println!("\n Found {} Combos", self.combos.len());
+            for combo in &self.combos {
+                println!("\nRequired Combopieces: {}", combo.num_pieces);
+                for piece in &combo.pieces {
+                    println!("{}", piece);
+                }
+                printsection("Prerequisites", &combo.prerequisites);
+                printsection("Steps", &combo.steps);
+                printsection("Results", &combo.results);
+            }
+            println!("------------------------------------------------------------");
+        }
+
+        if !self.near_misses.is_empty() {
+            println!("------------------------------------------------------------");
+            println!("\n Combos missing up to two pieces: {}", self.near_misses.len());
+            for combo in &self.near_misses {
+                println!("\n{} of {} pieces, missing: {}", combo.present, combo.pieces.len(), combo.missing.join(", "));
+                for piece in &combo.pieces {
+                    println!("{}", piece);
+                }
+                printsection("Results", &combo.results);
+            }
+            println!("------------------------------------------------------------");
+        }
+
+        println!("\n Draw probabilities {}: \n", self.probabilities.play);
+        for turn in &self.probabilities.turns {
+            match (turn.ramp, turn.draw) {
+                (Some(ramp), Some(draw)) => println!("Turn {}: Ramp {:.1}%, Draw {:.1}%", turn.turn, ramp * 100.0, draw * 100.0),
+                _ => println!("Turn {}: Not enough cards in library", turn.turn),
+            }
+        }
+        for combo in &self.combos {
+            if let Some(p) = combo.drawn_by_turn_four {
+                println!("Combo with {} pieces drawn naturally by turn 4: {:.2}%", combo.num_pieces, p * 100.0);
+            }
+            for turn in &combo.assembled {
+                if let Some(p) = turn.probability {
+                    println!("  assembled with tutors by turn {}: {:.2}%", turn.turn, p * 100.0);
+                }
+            }
+        }
+
+        println!("\n Tutorlinking: \n");
+        printlinks(&self.tutors.tutor);
+        println!("\n Fetches: \n");
+        printlinks(&self.tutors.fetches);
+        println!("\n Landramp: \n");
+        printlinks(&self.tutors.landramp);
+
+        println!("\n");
+        for focus in &self.foci {
+            println!("\nFor Focus: {} found cards: {}", focus.archetype, focus.cards.len());
+            for card in &focus.cards {
+                println!("{}", card);
+            }
+        }
+        for over in &self.overlaps {
+            println!("Overlap main focus: {}", over);
+        }
+        for combo in &self.combos {
+            println!("{:?}", combo.tutors);
+        }
+        println!("\n Payoffs for main focus:");
+        for payoff in &self.main_focus_payoff {
+            println!("{:?}", payoff);
+        }
+        println!("\n Payoffs for secondary focus:");
+        for payoff in &self.second_focus_payoff {
+            println!("{:?}", payoff);
+        }
+
+        println!("------------------------------------------------------------");
+        println!("Mana base, {:.0}% to cast spells on curve:", self.manabase.target * 100.0);
+        println!("Lands: {} of recommended {} (average mana value {:.2}, {} cheap ramp and draw)",
+            self.manabase.lands, self.manabase.recommended_lands, self.manabase.average_mv, self.manabase.cheap_ramp_draw);
+        for color in &self.manabase.colors {
+            let flag = if color.under_supplied { " <- under-supplied" } else { "" };
+            println!("{}: {} sources of {} needed for {} ({} pips){}", color.color, color.sources, color.required_sources,
+                color.hardest.as_deref().unwrap_or("-"), color.pips, flag);
+        }
+        println!("------------------------------------------------------------");
+
+        println!("Powerlevel: {}", self.powerlevel.score);
+        for factor in &self.powerlevel.factors {
+            println!("{}: {} -> {:.2} of {:.2} points", factor.name, factor.value, factor.points, factor.max_points);
+        }
+        println!("------------------------------------------------------------");
+    }
+}
+
+fn names(cards: &[&Card]) -> Vec<String> {
+    cards.iter().map(|card| card.name.to_string()).collect()
+}
+fn links(map: &std::collections::HashMap<&String, Vec<&Card>>) -> BTreeMap<String, Vec<String>> {
+    map.iter().map(|(card, targets)| (card.to_string(), names(targets))).collect()
+}
+fn printout(title: &str, cards: &[String]) {
+    println!("\n {}({}): ", title, cards.len());
+    for card in cards {
+        println!("{}", card);
+    }
+}
+fn printlinks(links: &BTreeMap<String, Vec<String>>) {
+    for (card, targets) in links {
+        println!("\n Targets for {}: \n", card);
+        for target in targets {
+            println!("{}", target);
+        }
+    }
+}
+fn printsection(title: &str, lines: &[String]) {
+    if !lines.is_empty() {
+        println!("{}:", title);
+        for line in lines {
+            println!("  {}", line);
+        }
+    }
+}
+
+impl Goldfish {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Goldfish results are always serializable")
+    }
+    pub fn println(&self) {
+        println!("Goldfished {} games over {} turns {}", self.games, self.turns, self.play);
+        println!("Keep rule {}", self.rule.describe());
+        println!("------------------------------------------------------------");
+        println!("Mulligans:");
+        printdistribution(&self.mulligans, self.games);
+        println!("------------------------------------------------------------");
+        println!("Commander cast on turn:");
+        printdistribution(&self.commander_turn, self.games);
+        println!("Not cast: {:.1}%", self.commander_not_cast as f64 * 100.0 / self.games.max(1) as f64);
+        println!("------------------------------------------------------------");
+        println!("Mana available and cards in hand per turn:");
+        for (turn, (mana, hand)) in self.mana.iter().zip(&self.hand).enumerate() {
+            println!("Turn {}: Mana {:.2}, Hand {:.2}", turn + 1, goldfish::mean(mana), goldfish::mean(hand));
+        }
+        println!("------------------------------------------------------------");
+    }
+}
+impl Evaluation {
+    pub fn println(&self) {
+        println!("------------------------------------------------------------");
+        println!("Rule {} over {} hands", self.rule, self.hands);
+        println!("Keeps seven: {:.1}%", self.keep_rate * 100.0);
+        println!("Expected mulligans: {:.2}", self.expected_mulligans);
+        printdistribution(&self.mulligans, self.hands);
+        for hand in &self.kept {
+            println!("Keep: {}", hand.join(", "));
+        }
+        for hand in &self.mulliganed {
+            println!("Mulligan: {}", hand.join(", "));
+        }
+    }
+}
+fn printdistribution(distribution: &Distribution, games: usize) {
+    for (value, count) in distribution {
+        println!("{}: {:.1}%", value, *count as f64 * 100.0 / games.max(1) as f64);
+    }
+}
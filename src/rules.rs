@@ -0,0 +1,178 @@
+/********************************** Classification Rules ***************************************/
+
+use std::{collections::HashMap, fs, path::Path, sync::OnceLock};
+use serde::Deserialize;
+use crate::types::{Card, CEerror, CEResult};
+use crate::query::Query;
+
+// Shipped rules, a user file is merged on top
+const DEFAULT_RULES: &str = include_str!("rules.json");
+
+static RULES: OnceLock<Rules> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    Draw,
+    Bounce,
+    Removal,
+    Boardwipe,
+    Lord,
+    Counter,
+    Payoff,
+    Recursion,
+    Reanimation,
+    Stax,
+    Fastmana,
+    Protection,
+    Wincon,
+    Engine,
+    Tax,
+    // Artifacts and creatures that make spells cheaper, counted as mana in ManaDist
+    CostReducer,
+    // Cards that search the library, the rest of the tutor module only looks at these
+    Tutor,
+    // Creature tutors like Neoform whose +1/+1 counter is no restriction on the target
+    UpgradeTutor,
+    // Tutors like Diabolic Intent that sacrifice a creature to find something else
+    SacrificeTutor,
+    // Tutors whose costs name card types although they find any card
+    AnyTutor,
+}
+
+/* One category in the rules file:
+     "removal": { "query": "key:destroy & restriction:target", "extend": true, "include": ["Chaos Warp"], "exclude": ["Pongify"] }
+   The query replaces the built in check, with extend it is added to it. Include wins over exclude, both by card name.
+   Categories: draw bounce removal boardwipe lord counter payoff recursion reanimation stax fastmana protection wincon
+   engine tax cost_reducer tutor upgrade_tutor sacrifice_tutor any_tutor */
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    query: Option<String>,
+    #[serde(default)]
+    extend: bool,
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Rule {
+    pub query: Option<Query>,
+    pub extend: bool,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+impl Rule {
+    pub fn matches(&self, card: &Card, builtin: fn(&Card) -> bool) -> bool {
+        let listed = |names: &Vec<String>| names.iter().any(|name| name.to_lowercase() == card.name.to_lowercase());
+
+        if listed(&self.include) {
+            return true;
+        }
+        if listed(&self.exclude) {
+            return false;
+        }
+        match &self.query {
+            Some(query) if self.extend => query.matches(card) || builtin(card),
+            Some(query) => query.matches(card),
+            None => builtin(card),
+        }
+    }
+    // Later files replace the query and add to the card lists
+    fn merge(&mut self, file: RuleFile) -> CEResult<()> {
+        if let Some(query) = file.query {
+            self.query = Some(Query::parse(&query)?);
+            self.extend = file.extend;
+        }
+        self.include.extend(file.include);
+        self.exclude.extend(file.exclude);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+    rules: HashMap<Category, Rule>,
+}
+impl Rules {
+    // Embedded defaults plus the user's file
+    pub fn load(path: Option<&Path>) -> CEResult<Rules> {
+        let mut rules = Rules::default();
+        rules.merge(DEFAULT_RULES, "default rules")?;
+
+        if let Some(path) = path {
+            match fs::read_to_string(path) {
+                Ok(t) => rules.merge(&t, &path.display().to_string())?,
+                Err(e) => return Err(CEerror::ConfigError(format!("Can not read rules {}: {}", path.display(), e))),
+            }
+        }
+        Ok(rules)
+    }
+    pub fn matches(&self, category: Category, card: &Card, builtin: fn(&Card) -> bool) -> bool {
+        match self.rules.get(&category) {
+            Some(rule) => rule.matches(card, builtin),
+            None => builtin(card),
+        }
+    }
+    fn merge(&mut self, json: &str, source: &str) -> CEResult<()> {
+        let file: HashMap<Category, RuleFile> = match serde_json::from_str(json) {
+            Ok(t) => t,
+            Err(e) => return Err(CEerror::ConfigError(format!("Invalid rules in {}: {}", source, e))),
+        };
+
+        for (category, rule) in file {
+            if let Err(e) = self.rules.entry(category).or_default().merge(rule) {
+                return Err(CEerror::ConfigError(format!("Invalid query for {:?} in {}: {}", category, source, e)));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Loads the rules once at startup, later calls keep the first ones
+pub fn init(path: Option<&Path>) -> CEResult<&'static Rules> {
+    if let Some(rules) = RULES.get() {
+        return Ok(rules);
+    }
+    let rules = Rules::load(path)?;
+    Ok(RULES.get_or_init(|| rules))
+}
+// Defaults only when init was not called
+pub fn get() -> &'static Rules {
+    RULES.get_or_init(|| Rules::load(None).expect("Embedded rules are valid"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(name: &str) -> Card {
+        let mut card = Card::new();
+        card.name = name.to_string();
+        card
+    }
+    #[test]
+    fn embedded_tutor_exceptions() {
+        let rules = Rules::load(None).unwrap();
+
+        assert!(!rules.matches(Category::Tutor, &card("Search for Azcanta"), |_| true));
+        assert!(rules.matches(Category::SacrificeTutor, &card("Razaketh, the Foulblooded"), |_| false));
+        assert!(rules.matches(Category::UpgradeTutor, &card("neoform"), |_| false));
+        assert!(!rules.matches(Category::UpgradeTutor, &card("Diabolic Intent"), |_| false));
+        assert!(rules.matches(Category::AnyTutor, &card("Scrapyard Recombiner"), |_| false));
+        assert!(!rules.matches(Category::AnyTutor, &card("Arcum Dagsson"), |_| false));
+    }
+    #[test]
+    fn user_rules_override_the_defaults() {
+        let mut rules = Rules::load(None).unwrap();
+        rules.merge(r#"{ "tutor": { "include": ["Search for Azcanta"] }, "sacrifice_tutor": { "exclude": ["Diabolic Intent"] }, "any_tutor": { "exclude": ["Diabolic Intent"] } }"#, "test").unwrap();
+
+        assert!(rules.matches(Category::Tutor, &card("Search for Azcanta"), |_| false));
+        assert!(!rules.matches(Category::SacrificeTutor, &card("Diabolic Intent"), |_| false));
+        assert!(rules.matches(Category::SacrificeTutor, &card("Arcum Dagsson"), |_| false));
+        assert!(!rules.matches(Category::AnyTutor, &card("Diabolic Intent"), |_| false));
+        assert!(rules.matches(Category::AnyTutor, &card("Razaketh, the Foulblooded"), |_| false));
+    }
+}
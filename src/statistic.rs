@@ -2,7 +2,9 @@
 
 
 pub mod basic {
-    use crate::{types::*, import::{self, combo::ComboResult}};
+    use crate::{types::*, import::{self, combo::ComboResult}, query::Query};
+    use crate::rules::{self, Category};
+    use crate::logic::oracle;
     use std::{collections::{BTreeMap, HashMap}};
     use crate::types::Colors;
     use strum::IntoEnumIterator;
@@ -14,45 +16,23 @@ pub mod basic {
         pub mana_dist: ManaDist<'deck>,
         pub effect: Effect<'deck>,
         pub combo: Vec<ComboResult>,
+        pub near_miss: Vec<ComboResult>,
     }
 
-    impl<'deck> Basic<'deck> {
-        pub fn println(&self) {
-            self.cardtype.println();
-            self.mana_dist.println();
-            self.effect.println();
-            
-            println!("------------------------------------------------------------");
-            println!("Manacost distribution:");
-            for (manacost, num) in &self.mana_cost {
-                println!("Manacost: {}, Number: {}", manacost, num.len());
-            }
-            println!("------------------------------------------------------------");
-
-           
-            if self.combo.len() != 0 {
-                println!("------------------------------------------------------------");
-                println!("\n Found {} Combos", self.combo.len());
-                for combo in &self.combo{
-                    println!("\nRequired Combopieces: {}", combo.num_pieces);
-                    for i in 1..=combo.num_pieces {
-                        println!("{}", combo.combo[i]);
-                    }
-                }
-                println!("------------------------------------------------------------");
-            }
-        }
-    }
-    //TODO: Think about a place to construct combo other than basic, since this leads to two times construction due to tutor making
     impl <'deck> Basic<'deck> {
-        pub fn new(deck: &Deck) -> Basic {
-            Basic {
+        pub fn new(deck: &Deck) -> CEResult<Basic> {
+            let (combo, near_miss) = import::combo::search(deck)?
+                .into_iter()
+                .partition(|combo| combo.is_complete());
+
+            Ok(Basic {
                 cardtype: cardtype(deck),
                 mana_cost: mana_cost(deck),
                 mana_dist: mana_distribution(deck),
                 effect: effect(deck),
-                combo: import::combo::search(deck).expect("unable to build combo"),
-            }
+                combo,
+                near_miss,
+            })
         }
     }
     #[derive(Debug)]
@@ -65,32 +45,6 @@ pub mod basic {
         pub instants: Vec<&'deck Card>,
         pub sorcerys: Vec<&'deck Card>,
     }
-    impl <'deck> Cardtype<'deck>{
-        pub fn println(&self) {
-            println!("------------------------------------------------------------");
-            println!("Your Deck listed by types:");
-            println!("\n Creatures({}):", self.creatures.len());
-            Cardtype::printout(&self.creatures);
-            println!("\n Enchantments({}):", self.enchantments.len());
-            Cardtype::printout(&self.enchantments);
-            println!("\n Artifacts({})", self.artifacts.len());
-            Cardtype::printout(&self.artifacts);
-            println!("\n Planeswalkers({}):", self.planeswalkers.len());
-            Cardtype::printout(&self.planeswalkers);
-            println!("\n Instants({}):", self.instants.len());
-            Cardtype::printout(&self.instants);
-            println!("\n Sorcerys({}):", self.sorcerys.len());
-            Cardtype::printout(&self.sorcerys);
-            println!("\n Lands({}): ", self.lands.len());
-            Cardtype::printout(&self.lands);
-            println!("------------------------------------------------------------");
-        }
-        fn printout(cards: &Vec<&'deck Card>) {
-            for card in cards {
-                println!("{}", card.name);
-            }
-        }
-    }
     #[derive(Debug)]
     pub struct ManaDist<'deck> {
         pub manacost: HashMap<Colors, u8>,
@@ -101,22 +55,6 @@ pub mod basic {
         pub lands: Vec<&'deck Card>,
     }
 
-    impl<'deck> ManaDist<'deck> {
-        pub fn println(&self) {
-            println!("------------------------------------------------------------");
-            println!("Manadistribution by Pips: ");
-            for (color, num) in &self.manacost {
-                println!("For {} found pips: {}", color, num);
-            }
-            println!("Manaproducer by Pips: "); 
-            for (color, num) in &self.manaprod {
-                println!("For {} found producer: {}", color, num);
-            }
-            println!("Ramp:{}", (self.dorks.len()+self.artifacts.len()+self.enchantments.len()));
-            println!("Distributed with Dorks({}), Artifacts({}), Land Ramp({}) and Enchantments({})", self.dorks.len(), self.artifacts.len(), self.lands.len(), self.enchantments.len());
-            println!("------------------------------------------------------------");
-        }
-    }
     #[derive(Debug)]
     pub struct Effect<'deck> {
         pub draw: Vec<&'deck Card>,
@@ -130,42 +68,10 @@ pub mod basic {
         pub reanimation: Vec<&'deck Card>,
         pub stax: Vec<&'deck Card>,
         pub fastmana: Vec<&'deck Card>,
-        
-    }
-
-    impl<'deck> Effect<'deck> {
-        pub fn println(&self) {
-            println!("------------------------------------------------------------");
-            println!("Your Deck, seperated by effects of your 99: ");
-            println!("\n Your draws({}): ", self.draw.len());
-            Effect::printout(&self.draw);
-            println!("\n Bounce-Spells({}): ", self.bounce.len());
-            Effect::printout(&self.bounce);
-            println!("\n Single target removal({}): ", self.removal.len());
-            Effect::printout(&self.removal);
-            println!("\n Boardwipes:({})", self.boardwipe.len());
-            Effect::printout(&self.boardwipe);
-            println!("\n Your buff effects({}): ", self.lord.len());
-            Effect::printout(&self.lord);
-            println!("\n Counterspells({}):", self.counter.len());
-            Effect::printout(&self.counter);
-            println!("\n Payoffs({}):", self.payoff.len());
-            Effect::printout(&self.payoff);
-            println!("\n Recursion({}):", self.recursion.len());
-            Effect::printout(&self.recursion);
-            println!("\n Reanimator-Spells({}):", self.reanimation.len());
-            Effect::printout(&self.reanimation);
-            println!("\n Stax({}): ", self.stax.len());
-            Effect::printout(&self.stax);
-            println!("\n Fast-Mana({}):", self.fastmana.len());
-            Effect::printout(&self.fastmana);
-            println!("------------------------------------------------------------");
-        }
-        fn printout(cards: &Vec<&'deck Card>) {
-            for card in cards {
-                println!("{}", card.name);
-            }
-        } 
+        pub protection: Vec<&'deck Card>,
+        pub wincon: Vec<&'deck Card>,
+        pub engine: Vec<&'deck Card>,
+        pub tax: Vec<&'deck Card>,
     }
 
     pub fn cardtype<'deck> (deck: &'deck Deck) -> Cardtype<'deck> {
@@ -204,12 +110,10 @@ pub mod basic {
     pub fn mana_cost(deck: &Deck) -> BTreeMap<u8, Vec<&Card>> {
         let mut mana_cost = BTreeMap::new();
 
+        // Each spell once whatever its types, artifact lands are lands
         for card in &deck.library {
-            for types in &card.cardtype{
-                match types {
-                    CardType::Land(_) => (),
-                    _ => mana_cost.entry(card.cmc as u8).or_insert(vec![card]).push(card),
-                }
+            if !card.cardtype.iter().any(|types| matches!(types, CardType::Land(_))) {
+                mana_cost.entry(card.cmc as u8).or_insert_with(Vec::new).push(card);
             }
         }
 
@@ -217,7 +121,7 @@ pub mod basic {
     }
     // TODO: refracture 
     pub fn mana_distribution(deck: &Deck) -> ManaDist{
-        
+        let rules = rules::get();
         let mut manacost: HashMap<Colors, u8> = HashMap::new();
         let mut manaprod: HashMap<Colors, u8> = HashMap::new();
         let mut dorks: Vec<&Card> = Vec::new();
@@ -236,9 +140,10 @@ pub mod basic {
 
         for card in libcom {
             for color in Colors::iter() {
-                if card.contains(&color, CardFields::ManaCost){
-                    // Pips per card, colored mana requirement
-                    *manacost.entry(color).or_insert(0) += card.mana_cost.matches(&color.to_string()).count() as u8;   
+                // Pips per card, colored mana requirement
+                let pips = card.mana_cost.required_pips(color);
+                if pips > 0 {
+                    *manacost.entry(color).or_insert(0) += pips;   
                 }
             }
             for color in Colors::iter() { 
@@ -300,10 +205,10 @@ pub mod basic {
                     None => (),
                 }
             }
-            if card.contains("search", CardFields::OracleText) 
-            && (card.contains("Land", CardFields::OracleText)
+            if card.contains("search", CardFields::OraclePhrase) 
+            && (card.contains("Land", CardFields::OraclePhrase)
                 || card.contains(CardType::Basic, CardFields::OracleType)
-                || card.contains("Forest", CardFields::OracleText) )
+                || card.contains("Forest", CardFields::OraclePhrase) )
             && card.contains(Zones::Battlefield, CardFields::Zones)
             && !card.contains(CardType::Land(None), CardFields::CardType) 
             && !card.contains(Keys::Destroy, CardFields::Keys){
@@ -324,14 +229,7 @@ pub mod basic {
                 dorks.push(card);
             }
             // Needed to get the mana reduction artifacts and effects
-            if card.contains(Keys::Cost, CardFields::Keys) 
-            && card.contains(Restrictions::Less, CardFields::Restrictions) 
-            && card.contains(Keys::Cast, CardFields::Keys) 
-            && card.contains(Keys::Spell, CardFields::Keys)
-            && !(card.contains(Zones::Battlefield, CardFields::Zones)  && !card.contains(Keys::ETB, CardFields::Keys) ) 
-            && card.name != String::from("The Great Henge")
-            && card.name != String::from("Mystic Remora")
-            {
+            if rules.matches(Category::CostReducer, card, is_cost_reducer) {
                 if card.contains(CardType::Artifact(None), CardFields::CardType){
                     artifacts.push(card);
                 } else {
@@ -342,7 +240,15 @@ pub mod basic {
         return ManaDist{ manacost, manaprod, dorks, artifacts, enchantments, lands };        
 
     }
+    fn is_cost_reducer(card: &Card) -> bool {
+        card.contains(Keys::Cost, CardFields::Keys) 
+            && card.contains(Restrictions::Less, CardFields::Restrictions) 
+            && card.contains(Keys::Cast, CardFields::Keys) 
+            && card.contains(Keys::Spell, CardFields::Keys)
+            && (!card.contains(Zones::Battlefield, CardFields::Zones) || card.contains(Keys::ETB, CardFields::Keys))
+    }
     pub fn effect(deck: &Deck) -> Effect {
+        let rules = rules::get();
         let mut draw: Vec<&Card> = Vec::new();
         let mut bounce: Vec<&Card> = Vec::new();
         let mut removal: Vec<&Card> = Vec::new();
@@ -354,156 +260,208 @@ pub mod basic {
         let mut reanimation: Vec<&Card> = Vec::new();
         let mut stax: Vec<&Card> = Vec::new();
         let mut fastmana: Vec<&Card> = Vec::new();
+        let mut protection: Vec<&Card> = Vec::new();
+        let mut wincon: Vec<&Card> = Vec::new();
+        let mut engine: Vec<&Card> = Vec::new();
+        let mut tax: Vec<&Card> = Vec::new();
       
         for card in &deck.library {
             match &card.backside {
                 Some(backside) => {
-                    if is_draw(&backside){
-                        draw.push(&backside);
+                    if rules.matches(Category::Draw, backside, is_draw){
+                        draw.push(backside);
                     }
-                    if is_removal(&backside){ 
-                        removal.push(&backside);
+                    if rules.matches(Category::Removal, backside, is_removal){ 
+                        removal.push(backside);
                     }
-                    if is_counter(&backside){
-                        counter.push(&backside);
+                    if rules.matches(Category::Counter, backside, is_counter){
+                        counter.push(backside);
                     }
-                    if  is_bounce(&backside){
-                        bounce.push(&backside);
+                    if  rules.matches(Category::Bounce, backside, is_bounce){
+                        bounce.push(backside);
                     }
-                    if is_recursion(&backside) {
-                        recursion.push(&backside);
+                    if rules.matches(Category::Recursion, backside, is_recursion) {
+                        recursion.push(backside);
                     } 
-                    if is_reanimation(&backside) {
-                        reanimation.push(&backside); 
+                    if rules.matches(Category::Reanimation, backside, is_reanimation) {
+                        reanimation.push(backside); 
                     }
-                    if is_boardwipe(&backside){
-                        boardwipe.push(&backside);
+                    if rules.matches(Category::Boardwipe, backside, is_boardwipe){
+                        boardwipe.push(backside);
                     }
-                    if is_payoff(&backside) { 
-                        payoff.push(&backside);
+                    if rules.matches(Category::Payoff, backside, is_payoff) { 
+                        payoff.push(backside);
                     }
-                    if is_lord(&backside) { 
-                        lord.push(&backside);
+                    if rules.matches(Category::Lord, backside, is_lord) { 
+                        lord.push(backside);
                     }
-                    if is_stax(&backside) {
-                        stax.push(&backside);
+                    if rules.matches(Category::Stax, backside, is_stax) {
+                        stax.push(backside);
                     } 
-                    if is_fastmana(&backside) {
-                        fastmana.push(&backside)
+                    if rules.matches(Category::Fastmana, backside, is_fastmana) {
+                        fastmana.push(backside)
+                    }
+                    if rules.matches(Category::Protection, backside, is_protection) {
+                        protection.push(backside);
+                    }
+                    if rules.matches(Category::Wincon, backside, is_wincon) {
+                        wincon.push(backside);
+                    }
+                    if rules.matches(Category::Engine, backside, is_engine) {
+                        engine.push(backside);
+                    }
+                    if rules.matches(Category::Tax, backside, is_tax) {
+                        tax.push(backside);
                     }
                 },
                 None => (),
             }
-            if is_draw(card){
+            if rules.matches(Category::Draw, card, is_draw){
                 draw.push(card);
             }
-            if is_removal(card){ 
+            if rules.matches(Category::Removal, card, is_removal){ 
                 removal.push(card);
             }
-            if is_counter(card){
+            if rules.matches(Category::Counter, card, is_counter){
                 counter.push(card);
             }
-            if  is_bounce(card){
+            if  rules.matches(Category::Bounce, card, is_bounce){
                 bounce.push(card);
             }
-            if is_recursion(card) {
+            if rules.matches(Category::Recursion, card, is_recursion) {
                 recursion.push(card);
             } 
-            if is_reanimation(card) {
+            if rules.matches(Category::Reanimation, card, is_reanimation) {
                 reanimation.push(card); 
             }
-            if is_boardwipe(card){
+            if rules.matches(Category::Boardwipe, card, is_boardwipe){
                 boardwipe.push(card);
             }
-            if is_payoff(card) { 
+            if rules.matches(Category::Payoff, card, is_payoff) { 
                 payoff.push(card);
             }
-            if is_lord(card) { 
+            if rules.matches(Category::Lord, card, is_lord) { 
                 lord.push(card);
             }
-            if is_stax(card) {
+            if rules.matches(Category::Stax, card, is_stax) {
                 stax.push(card);
             }
-            if is_fastmana(card) {
+            if rules.matches(Category::Fastmana, card, is_fastmana) {
                 fastmana.push(card);
             }
+            if rules.matches(Category::Protection, card, is_protection) {
+                protection.push(card);
+            }
+            if rules.matches(Category::Wincon, card, is_wincon) {
+                wincon.push(card);
+            }
+            if rules.matches(Category::Engine, card, is_engine) {
+                engine.push(card);
+            }
+            if rules.matches(Category::Tax, card, is_tax) {
+                tax.push(card);
+            }
         }
 
         for card in &deck.commander {
             match &card.backside {
                 Some(backside) => {
-                    if is_draw(&backside){
-                        draw.push(&backside);
+                    if rules.matches(Category::Draw, backside, is_draw){
+                        draw.push(backside);
                     }
-                    if is_removal(&backside){ 
-                        removal.push(&backside);
+                    if rules.matches(Category::Removal, backside, is_removal){ 
+                        removal.push(backside);
                     }
-                    if is_counter(&backside){
-                        counter.push(&backside);
+                    if rules.matches(Category::Counter, backside, is_counter){
+                        counter.push(backside);
                     }
-                    if  is_bounce(&backside){
-                        bounce.push(&backside);
+                    if  rules.matches(Category::Bounce, backside, is_bounce){
+                        bounce.push(backside);
                     }
-                    if is_recursion(&backside) {
-                        recursion.push(&backside);
+                    if rules.matches(Category::Recursion, backside, is_recursion) {
+                        recursion.push(backside);
                     } 
-                    if is_reanimation(&backside) {
-                        reanimation.push(&backside); 
+                    if rules.matches(Category::Reanimation, backside, is_reanimation) {
+                        reanimation.push(backside); 
                     }
-                    if is_boardwipe(&backside){
-                        boardwipe.push(&backside);
+                    if rules.matches(Category::Boardwipe, backside, is_boardwipe){
+                        boardwipe.push(backside);
                     }
-                    if is_payoff(&backside) { 
-                        payoff.push(&backside);
+                    if rules.matches(Category::Payoff, backside, is_payoff) { 
+                        payoff.push(backside);
                     }
-                    if is_lord(&backside) { 
-                        lord.push(&backside);
+                    if rules.matches(Category::Lord, backside, is_lord) { 
+                        lord.push(backside);
                     }
-                    if is_stax(&backside) {
-                        stax.push(&backside);
+                    if rules.matches(Category::Stax, backside, is_stax) {
+                        stax.push(backside);
                     } 
-                    if is_fastmana(&backside) {
-                        fastmana.push(&backside)
+                    if rules.matches(Category::Fastmana, backside, is_fastmana) {
+                        fastmana.push(backside)
+                    }
+                    if rules.matches(Category::Protection, backside, is_protection) {
+                        protection.push(backside);
+                    }
+                    if rules.matches(Category::Wincon, backside, is_wincon) {
+                        wincon.push(backside);
+                    }
+                    if rules.matches(Category::Engine, backside, is_engine) {
+                        engine.push(backside);
+                    }
+                    if rules.matches(Category::Tax, backside, is_tax) {
+                        tax.push(backside);
                     }
                 },
                 None => (),
             }
-            if is_draw(card){
+            if rules.matches(Category::Draw, card, is_draw){
                 draw.push(card);
             }
-            if is_removal(card){ 
+            if rules.matches(Category::Removal, card, is_removal){ 
                 removal.push(card);
             }
-            if is_counter(card){
+            if rules.matches(Category::Counter, card, is_counter){
                 counter.push(card);
             }
-            if  is_bounce(card){
+            if  rules.matches(Category::Bounce, card, is_bounce){
                 bounce.push(card);
             }
-            if is_recursion(card) {
+            if rules.matches(Category::Recursion, card, is_recursion) {
                 recursion.push(card);
             } 
-            if is_reanimation(card) {
+            if rules.matches(Category::Reanimation, card, is_reanimation) {
                 reanimation.push(card); 
             }
-            if is_boardwipe(card){
+            if rules.matches(Category::Boardwipe, card, is_boardwipe){
                 boardwipe.push(card);
             }
-            if is_payoff(card) { 
+            if rules.matches(Category::Payoff, card, is_payoff) { 
                 payoff.push(card);
             }
-            if is_lord(card) { 
+            if rules.matches(Category::Lord, card, is_lord) { 
                 lord.push(card);
             }
-            if is_stax(card) {
+            if rules.matches(Category::Stax, card, is_stax) {
                 stax.push(card);
             }
-            if is_fastmana(card) {
+            if rules.matches(Category::Fastmana, card, is_fastmana) {
                 fastmana.push(card);
             } 
+            if rules.matches(Category::Protection, card, is_protection) {
+                protection.push(card);
+            }
+            if rules.matches(Category::Wincon, card, is_wincon) {
+                wincon.push(card);
+            }
+            if rules.matches(Category::Engine, card, is_engine) {
+                engine.push(card);
+            }
+            if rules.matches(Category::Tax, card, is_tax) {
+                tax.push(card);
+            }
         }
 
-        return Effect{draw, bounce, removal, boardwipe, lord, counter, payoff, recursion, reanimation, stax, fastmana};
+        Effect{draw, bounce, removal, boardwipe, lord, counter, payoff, recursion, reanimation, stax, fastmana, protection, wincon, engine, tax}
     }
     fn is_draw(card: &Card) -> bool {
         if (( (card.contains(Keys::Draw, CardFields::Keys) && !card.contains(Restrictions::CanT, CardFields::Restrictions) )
@@ -538,7 +496,7 @@ pub mod basic {
         }
     } 
     fn is_removal(card: &Card) -> bool {
-        if( card.contains(Keys::Destroy, CardFields::Keys) || (card.contains(Keys::Exile, CardFields::Keys) && !card.contains(&*card.name, CardFields::OracleText) && !card.contains(Keys::Return, CardFields::Keys))) 
+        if( card.contains(Keys::Destroy, CardFields::Keys) || (card.contains(Keys::Exile, CardFields::Keys) && !card.contains("CARDNAME", CardFields::OracleTemplate) && !card.contains(Keys::Return, CardFields::Keys))) 
         && card.contains(Restrictions::Target, CardFields::Restrictions) 
         && ( !(card.contains(Zones::Hand, CardFields::Zones) && !card.contains(Restrictions::AnyNumber, CardFields::Restrictions))|| card.contains(Keywords::Evoke, CardFields::Keywords) )
             // Overload boardwipes are removal too || Ugly hack to exlcude Sevinnes Reclamation
@@ -549,7 +507,7 @@ pub mod basic {
         } 
     } 
     fn is_boardwipe(card: &Card) -> bool {
-        if( card.contains(Keys::Destroy, CardFields::Keys) || (card.contains(Keys::Exile, CardFields::Keys) && !card.contains(&*card.name, CardFields::OracleText) && !card.contains(Keys::Return, CardFields::Keys))) 
+        if( card.contains(Keys::Destroy, CardFields::Keys) || (card.contains(Keys::Exile, CardFields::Keys) && !card.contains("CARDNAME", CardFields::OracleTemplate) && !card.contains(Keys::Return, CardFields::Keys))) 
         && card.contains(Restrictions::Target, CardFields::Restrictions) 
         && ( !card.contains(Zones::Hand, CardFields::Zones) || card.contains(Keywords::Evoke, CardFields::Keywords) )
         && card.contains(Keywords::Overload, CardFields::Keywords) {
@@ -558,7 +516,7 @@ pub mod basic {
         || card.contains(Restrictions::All, CardFields::Restrictions) 
         || card.contains(Restrictions::Every, CardFields::Restrictions) ) 
         && (card.contains(Keys::Destroy, CardFields::Keys)
-            || (card.contains(Keys::Exile, CardFields::Keys) && !card.contains(&*card.name, CardFields::OracleText ) && !card.contains(Keys::Return, CardFields::Keys) && !card.contains(Keys::Cast, CardFields::Keys)) 
+            || (card.contains(Keys::Exile, CardFields::Keys) && !card.contains("CARDNAME", CardFields::OracleTemplate) && !card.contains(Keys::Return, CardFields::Keys) && !card.contains(Keys::Cast, CardFields::Keys)) 
             || ( card.contains(Keys::Return, CardFields::Keys) && !card.contains(Keys::Exile, CardFields::Keys) )
             || card.contains(Restrictions::MinusXX, CardFields::Restrictions) ) 
         && !card.contains(Keywords::Overload, CardFields::Keywords) 
@@ -603,29 +561,28 @@ pub mod basic {
         }
  
     } 
-    fn is_payoff(card: &Card) -> bool {
-        if ( card.contains(Restrictions::Whenever, CardFields::Restrictions) && !card.contains(Keys::Tapped, CardFields::Keys) )
-        && (card.contains(Keys::ETB, CardFields::Keys) 
-            || (card.contains(Keys::Cast, CardFields::Keys) && card.contains(Restrictions::You, CardFields::Restrictions))
-            || (card.contains(Keys::Copy, CardFields::Keys) && card.contains(Restrictions::You, CardFields::Restrictions))
-            || (card.contains(Keys::Play, CardFields::Keys) && card.contains(Restrictions::You, CardFields::Restrictions))
-            || (card.contains(Keys::Damage, CardFields::Keys) && !card.contains(Restrictions::You, CardFields::Restrictions)) 
-            || card.contains(Restrictions::Die, CardFields::Restrictions)
-            || (card.contains(Keys::Discard, CardFields::Keys) && !card.contains(Restrictions::Drawstep, CardFields::Restrictions) )
-            || (card.contains(Restrictions::GainLife, CardFields::Restrictions) && card.contains(Restrictions::You, CardFields::Restrictions))
-            || ( (card.contains(Keys::Draw, CardFields::Keys) &&!card.contains(Restrictions::Drawstep, CardFields::Restrictions)) && card.contains(Restrictions::You, CardFields::Restrictions) ))
-        || (card.contains(Keys::Sacrifice, CardFields::Keys)   
-            && card.contains(CardType::Creature(None), CardFields::OracleType) 
-            && !card.contains(Keys::Search, CardFields::Keys)
-            && !card.contains(&card.name.to_string(), CardFields::OracleText))
-        || (card.contains(Keys::Sacrifice, CardFields::Keys) 
-            && card.contains(CardType::Artifact(None), CardFields::OracleType)
-            && !card.contains(Keys::Search, CardFields::Keys)
-            && !card.contains(&card.name.to_string(), CardFields::OracleText )){ 
-                return true;
-        } else {
-            return false;
-        }        
+    pub(super) fn is_payoff(card: &Card) -> bool {
+        let you = Query::restriction(Restrictions::You);
+        let trigger = Query::key(Keys::ETB)
+            | (Query::key(Keys::Cast) & you.clone())
+            | (Query::key(Keys::Copy) & you.clone())
+            | (Query::key(Keys::Play) & you.clone())
+            | (Query::key(Keys::Damage) & !you.clone())
+            | Query::restriction(Restrictions::Die)
+            | (Query::key(Keys::Discard) & !Query::restriction(Restrictions::Drawstep))
+            | (Query::restriction(Restrictions::GainLife) & you.clone())
+            | (Query::key(Keys::Draw) & !Query::restriction(Restrictions::Drawstep) & you);
+        // Sac outlets for creatures and artifacts that are not the card itself
+        let outlet = |fodder: CardType| Query::key(Keys::Sacrifice)
+            & Query::oracle_type(fodder)
+            & !Query::key(Keys::Search)
+            & !Query::refers_to_self();
+
+        let payoff = (Query::restriction(Restrictions::Whenever) & !Query::key(Keys::Tapped) & trigger)
+            | outlet(CardType::Creature(None))
+            | outlet(CardType::Artifact(None));
+
+        payoff.matches(card)
     } 
     fn is_recursion(card: &Card) -> bool {
         if (card.contains(Keys::Return, CardFields::Keys) ||  card.contains(Keys::Put, CardFields::Keys) )
@@ -641,7 +598,7 @@ pub mod basic {
     } 
     fn is_reanimation(card: &Card) -> bool {
         if( (card.contains(Keys::Return, CardFields::Keys) 
-            && !(card.contains(card.name.to_string(), CardFields::OracleText) && !card.contains(Keys::Tapped, CardFields::Keys) )) 
+            && (!card.contains("CARDNAME", CardFields::OracleTemplate) || card.contains(Keys::Tapped, CardFields::Keys) )) 
             ||  (card.contains(Keys::Put, CardFields::Keys) ))
         && card.contains(Zones::Graveyard, CardFields::Zones) 
         && (card.contains(Zones::Battlefield, CardFields::Zones) && !card.contains(Zones::Hand, CardFields::Zones) )
@@ -654,29 +611,30 @@ pub mod basic {
             return false;
         }
     } 
-    fn is_stax(card: &Card) -> bool {
-        if (card.contains(Restrictions::CanT, CardFields::Restrictions) 
-            || (card.contains(Keys::Player, CardFields::Restrictions)&& card.contains(Restrictions::Each, CardFields::Restrictions))
-            || (card.contains(Keys::Opponent, CardFields::Keys) && card.contains(Restrictions::Each, CardFields::Restrictions))
-            || (card.contains(Keys::Cost, CardFields::Keys) 
-                && card.contains(Restrictions::More, CardFields::Restrictions) ) 
-            || (card.contains(Restrictions::CanT, CardFields::Restrictions) && card.contains(Keys::Activate, CardFields::Keys) ) 
-            || (card.contains(Restrictions::Non, CardFields::Restrictions) && card.contains(CardType::Basic, CardFields::OracleType) )
-            || (card.contains(CardType::Land(None), CardFields::OracleType) && card.contains(Restrictions::Dont, CardFields::Restrictions) && card.contains(Restrictions::Untap, CardFields::Restrictions)))
-        && (card.contains(CardType::Creature(None), CardFields::CardType)
-            || card.contains(CardType::Artifact(None), CardFields::CardType) 
-            || card.contains(CardType::Enchantment(None), CardFields::CardType)
-            || card.contains(CardType::Planeswalker, CardFields::CardType))
-        && !card.contains(Keys::Add, CardFields::Keys)
-        && !card.contains(Keys::ETB, CardFields::Keys)
-        && !card.contains(&card.name, CardFields::OracleText){
-                return true;
-            }
-            return false;
+    pub(super) fn is_stax(card: &Card) -> bool {
+        let each = Query::restriction(Restrictions::Each);
+        let lock = Query::restriction(Restrictions::CanT)
+            | (Query::key(Keys::Opponent) & each)
+            | (Query::key(Keys::Cost) & Query::restriction(Restrictions::More))
+            | (Query::restriction(Restrictions::Non) & Query::oracle_type(CardType::Basic))
+            | (Query::oracle_type(CardType::Land(None)) & Query::restriction(Restrictions::Dont) & Query::restriction(Restrictions::Untap));
+        let permanent = Query::any_of(vec![
+            Query::cardtype(CardType::Creature(None)),
+            Query::cardtype(CardType::Artifact(None)),
+            Query::cardtype(CardType::Enchantment(None)),
+            Query::cardtype(CardType::Planeswalker),
+        ]);
+
+        let stax = lock & permanent
+            & !Query::key(Keys::Add)
+            & !Query::key(Keys::ETB)
+            & !Query::refers_to_self();
+
+        stax.matches(card)
     }
     fn is_fastmana(card: &Card) -> bool {
         if (( card.contains(Keys::Sacrifice, CardFields::Keys) 
-                && card.contains(&card.name, CardFields::OracleText) 
+                && card.contains("CARDNAME", CardFields::OracleTemplate) 
                 && !card.contains(Keys::Search, CardFields::Keys)
                 && !card.contains(CardType::Land(None), CardFields::CardType) )
             || ( (card.contains(CardType::Instant(None), CardFields::CardType) 
@@ -684,7 +642,7 @@ pub mod basic {
                 || card.contains(CardType::Sorcery(None), CardFields::CardType) 
                 || (card.contains(CardType::Creature(None), CardFields::CardType) 
                 && !card.contains(Keys::Tap, CardFields::Keys) )))
-        && (card.contains(Keys::Add, CardFields::Keys) || card.contains(ArtifactSubtype::Treasure, CardFields::OracleText) )
+        && (card.contains(Keys::Add, CardFields::Keys) || card.contains(ArtifactSubtype::Treasure, CardFields::OraclePhrase) )
         && !card.contains(Keys::Additional, CardFields::Keys)
         && !card.contains(Keywords::Retrace, CardFields::Keywords) 
         && !card.contains(Keys::Draw, CardFields::Keys){  
@@ -694,6 +652,70 @@ pub mod basic {
         }
 
     }
+    // Grants hexproof, indestructible and the like to other permanents or phases them out
+    fn is_protection(card: &Card) -> bool {
+        let protective = [Keywords::Hexproof, Keywords::Indestructible, Keywords::Shroud, Keywords::Protection, Keywords::Ward, Keywords::Phasing];
+        // Lightning Greaves: "Equipped creature has shroud", the creature itself having a keyword is no protection spell
+        let attached = card.cardtype.iter().any(|t| match t {
+            CardType::Artifact(Some(subtypes)) => subtypes.contains(&ArtifactSubtype::Equipment),
+            CardType::Enchantment(Some(subtypes)) => subtypes.contains(&EnchantmentSubtype::Aura),
+            _ => false,
+        });
+
+        card.abilities.iter().any(|ability| {
+            (ability.keywords.iter().any(|keyword| protective.contains(keyword))
+                && (oracle::contains(&ability.text, "gain") || oracle::contains(&ability.text, "have") || (attached && oracle::contains(&ability.text, "has"))))
+            || oracle::contains(&ability.text, "phase out")
+        })
+    }
+    /* Alternate wins and cards that end the game, "you win the game" or "loses the game", plus the
+       repeatable drain and ping triggers that turn an infinite loop into a win, Blood Artist drains a target on every death */
+    fn is_wincon(card: &Card) -> bool {
+        card.abilities.iter().any(|ability| {
+            let text = &ability.text;
+            let ends_game = (oracle::contains(text, "win the game") || oracle::contains(text, "loses the game"))
+                && !oracle::contains(text, "can't win the game")
+                && !oracle::contains(text, "can't lose the game");
+            let outlet = ability.kind == AbilityKind::Triggered
+                && oracle::contains(text, "whenever")
+                && (oracle::contains(text, "each opponent loses") || oracle::contains(text, "damage to each opponent")
+                    || (oracle::contains(text, "dies") && (oracle::contains(text, "target player loses") || oracle::contains(text, "target opponent loses"))));
+
+            ends_game || outlet
+        })
+    }
+    /* Card advantage every turn instead of once: draw or impulse draw on an activated ability, a whenever or
+       at the beginning of trigger, or a static extra draw. When triggers are one shot enter the battlefield draws,
+       like abilities that sacrifice the card itself. Draw step texts only count with additional cards, Howling Mine */
+    fn is_engine(card: &Card) -> bool {
+        card.abilities.iter().any(|ability| {
+            let text = &ability.text;
+            let advantage = (ability.keys.contains(&Keys::Draw) && (!oracle::contains(text, "draw step") || oracle::contains(text, "additional card")))
+                || (oracle::contains(text, "exile the top") && (oracle::contains(text, "you may play") || oracle::contains(text, "you may cast")));
+            let repeatable = match ability.kind {
+                AbilityKind::Activated => match &ability.cost {
+                    Some(cost) => !oracle::contains(&oracle::templated(cost, &card.name), "sacrifice CARDNAME"),
+                    None => true,
+                },
+                AbilityKind::Triggered => oracle::contains(text, "whenever") || oracle::contains(text, "at the beginning"),
+                AbilityKind::Static => oracle::contains(text, "additional card"),
+                _ => false,
+            };
+
+            advantage && repeatable
+        })
+    }
+    // Symmetrical taxes and hatebears: spells or abilities cost more, opponents pay or their permanents enter tapped
+    fn is_tax(card: &Card) -> bool {
+        let permanent = !card.contains(CardType::Instant(None), CardFields::CardType) && !card.contains(CardType::Sorcery(None), CardFields::CardType);
+        let taxes = ["more to cast", "more to activate", "unless that player pays", "unless its controller pays", "can't attack you unless"];
+
+        permanent && card.abilities.iter().any(|ability| {
+            taxes.iter().any(|tax| oracle::contains(&ability.text, tax))
+                || (oracle::contains(&ability.text, "enter the battlefield tapped") && oracle::contains(&ability.text, "opponents"))
+                || (oracle::contains(&ability.text, "enter tapped") && oracle::contains(&ability.text, "opponents"))
+        })
+    }
 }
 
 
@@ -954,44 +976,16 @@ pub mod archetype {
     }
 
     pub struct Consistency<'deck> {
-        overlaps: Overlap<'deck>,
-        combo_tutor: Vec<Vec<&'deck String>>,
-        main_focus_payoff: Vec<&'deck Card>,
-        second_focus_payoff: Vec<&'deck Card>
+        pub overlaps: Overlap<'deck>,
+        pub combo_tutor: Vec<Vec<&'deck String>>,
+        pub main_focus_payoff: Vec<&'deck Card>,
+        pub second_focus_payoff: Vec<&'deck Card>
     }
 
-    impl Consistency<'_> {
-        pub fn println(&self) {
-            println!("\n");
-        
-            for focus in &self.overlaps.sorted_foci {
-                println!("\nFor Focus: {:?} found cards: {}", focus.archetype, focus.cards.len());
-                for card in &focus.cards {
-                    println!("{}", card.name);
-                }
-            }
-            for over in &self.overlaps.overlaps{
-                println!("Overlap main focus: {}", over);
-            }
-    
-            for combo in &self.combo_tutor {
-                println!("{:?}", combo);
-            }
-    
-            println!("\n Payoffs for main focus:");
-            for payoff in &self.main_focus_payoff {
-                println!("{:?}", payoff.name);
-            }
-    
-            println!("\n Payoffs for secondary focus:");
-            for payoff in &self.second_focus_payoff {
-                println!("{:?}", payoff.name);
-            }
-        }
-    }
+
     // here we try to figure out all possible options a commander could be build, from there we try to match out of the 99 which way (or none) the particular deck 
     // is build
-    pub fn from<'deck>(deck: &'deck Deck, basics: &'deck Basic, tutor: crate::tutor::Tutor<'deck>) -> Consistency<'deck>{  
+    pub fn from<'deck>(deck: &'deck Deck, basics: &'deck Basic, tutor: &crate::tutor::Tutor<'deck>) -> Consistency<'deck>{  
         
         consistency(focus(deck, commander_theme(deck), basics), &basics.combo ,basics, tutor)
     }
@@ -1084,7 +1078,7 @@ pub mod archetype {
              }
             
             if commander.contains(Restrictions::Get, CardFields::Restrictions) 
-            && commander.contains(&commander.name, CardFields::OracleText){result.push(Archetype::Voltron)}
+            && commander.contains("CARDNAME", CardFields::OracleTemplate){result.push(Archetype::Voltron)}
             
             if commander.contains(Keys::Discard, CardFields::Keys) 
             || commander.contains(Keys::Draw, CardFields::Keys) {result.push(Archetype::Wheel)}
@@ -1120,7 +1114,7 @@ pub mod archetype {
        result
     }
     // Figure out overlap between detected Archetypes and sort out irrelevant types (maybe len() < 5 => no relevance)
-    fn consistency<'deck>(foci: Vec<Focus<'deck>>, combos: &'deck Vec<ComboResult>, basics: &'deck Basic ,tutor: crate::tutor::Tutor<'deck>) -> Consistency<'deck> {
+    fn consistency<'deck>(foci: Vec<Focus<'deck>>, combos: &'deck Vec<ComboResult>, basics: &'deck Basic ,tutor: &crate::tutor::Tutor<'deck>) -> Consistency<'deck> {
 
         let overlaps = Focus::overlaps(foci);
         // gives back tutor names targeting combopieces in the same order as ComboResult
@@ -1147,7 +1141,7 @@ pub mod archetype {
                 let mut interaction = false;
                 let mut already_found = false;
                 for text in block{
-                    if card.contains(&text, CardFields::OracleText) {    
+                    if card.contains(text, CardFields::OraclePhrase) {    
                         hit += 1;
                     }
                 }
@@ -1218,6 +1212,8 @@ pub mod tutor {
     use strum::IntoEnumIterator;
 
     use crate::types::{Card, Deck, *};
+    use crate::query::{Query, Compare};
+    use crate::rules::{self, Category};
 
     use crate::statistic::basic;
 
@@ -1247,7 +1243,7 @@ pub mod tutor {
 
             for combo in combos {
                 let mut buffer = Vec::<&String>::new();
-                for piece in &combo.combo {    
+                for piece in combo.pieces() {    
                     if piece != &"".to_string() {
                         for hit in self.contains(piece) {
                             if hit != &"".to_string() {
@@ -1268,17 +1264,12 @@ pub mod tutor {
         let mut fetches: HashMap<&'deck String, Vec<&'deck Card>> = HashMap::new();
         let mut landramp: HashMap<&'deck String, Vec<&'deck Card>> = HashMap::new();
 
-        //TODO: Just not pretty because combos get constructed twice. Need of doing it differently here, maybe pass through the sorted deck
-        let mut sdeck = basic::Basic::new(&deck).cardtype;
+        let mut sdeck = basic::cardtype(deck);
+
+        let rules = rules::get();
 
         for card in &deck.library {           
-            if card.contains(Keys::Search, CardFields::Keys) 
-            && !card.contains(Keys::Opponent, CardFields::Keys)
-            && !card.contains(Restrictions::CanT, CardFields::Restrictions)
-            && !card.contains(String::from("Research"), CardFields::OracleText)
-            // Tutor can force you to sacrifce a permanent of the chosen type. Diabolic intent and Arcum Dagson force you to sacrice a creature to find a another type 
-            // && card.name != String::from("Diabolic Intent") 
-            && card.name != String::from("Search for Azcanta"){
+            if rules.matches(Category::Tutor, card, is_tutor) {
                 let mut buffer: Vec<&Card> = Vec::new();
        
                 match &card.oracle_types {
@@ -1292,9 +1283,9 @@ pub mod tutor {
                 if card.cardtype.contains(&CardType::Land(None)) 
                 && card.contains(Zones::Battlefield, CardFields::Zones){
                     fetches.insert(&card.name, buffer);
-                } else if card.contains("Land", CardFields::OracleText)
-                || card.contains("Forest", CardFields::OracleText)
-                || card.contains("Basic", CardFields::OracleText)
+                } else if card.contains("Land", CardFields::OraclePhrase)
+                || card.contains("Forest", CardFields::OraclePhrase)
+                || card.contains("Basic", CardFields::OraclePhrase)
                 {
                     landramp.insert(&card.name, buffer);
                 } else {
@@ -1304,10 +1295,7 @@ pub mod tutor {
         }
         
         for card in &deck.commander {
-            if card.contains(Keys::Search, CardFields::Keys) 
-            && !card.contains(Keys::Opponent, CardFields::Keys)
-            && !card.contains(Restrictions::CanT, CardFields::Restrictions)
-            && !card.contains(String::from("Research"), CardFields::OracleText) {
+            if rules.matches(Category::Tutor, card, is_tutor) {
                 let mut buffer: Vec<&Card> = Vec::new();
        
                 match &card.oracle_types {
@@ -1321,9 +1309,9 @@ pub mod tutor {
                 if card.cardtype.contains(&CardType::Land(None)) 
                 && card.contains(Zones::Battlefield, CardFields::Zones){
                     fetches.insert(&card.name, buffer);
-                } else if (card.contains("Land", CardFields::OracleText)
-                || card.contains("Forest", CardFields::OracleText)
-                || card.contains("Basic", CardFields::OracleText))
+                } else if (card.contains("Land", CardFields::OraclePhrase)
+                || card.contains("Forest", CardFields::OraclePhrase)
+                || card.contains("Basic", CardFields::OraclePhrase))
                 && card.contains(Zones::Battlefield, CardFields::Zones)
                 {
                     landramp.insert(&card.name, buffer);
@@ -1339,6 +1327,13 @@ pub mod tutor {
             landramp,
         }
     }
+    // Search for Azcanta and the like only look at the top of the library, the rules file excludes them
+    fn is_tutor(card: &Card) -> bool {
+        card.contains(Keys::Search, CardFields::Keys) 
+            && !card.contains(Keys::Opponent, CardFields::Keys)
+            && !card.contains(Restrictions::CanT, CardFields::Restrictions)
+            && !card.contains("Research", CardFields::OracleWord)
+    }
     // There is a bug with the World Tree TODO: Make World tree only linking God-Cards
     fn link_target<'deck>(tutor: &Card, deck: &'deck Deck, sdeck: &mut basic::Cardtype<'deck>, typ: &CardType) -> Vec<&'deck Card> {
         let mut targets: Vec<&'deck Card> = Vec::new();
@@ -1362,9 +1357,9 @@ pub mod tutor {
             },
             CardType::Creature(_) => {
                 if tutor.contains(Keys::With, CardFields::Keys)
-                && !((tutor.contains(Keys::Exile, CardFields::Keys) && !tutor.contains(&tutor.name, CardFields::OracleText))
+                && !((tutor.contains(Keys::Exile, CardFields::Keys) && !tutor.contains("CARDNAME", CardFields::OracleTemplate))
                     || tutor.contains(Keys::Token, CardFields::Keys)
-                    || (tutor.contains(Keys::Counter, CardFields::Keys) && tutor.name != String::from("Neoform") && tutor.name != String::from("Eldritch Evolution"))) { 
+                    || (tutor.contains(Keys::Counter, CardFields::Keys) && !rules::get().matches(Category::UpgradeTutor, tutor, |_| false))) { 
                     targets.append(&mut restrictions(deck, tutor, sdeck, CardType::Creature(None)));
                 } else {
                     if !(tutor.contains(Keys::Exile, CardFields::Keys) 
@@ -1373,9 +1368,8 @@ pub mod tutor {
                     || tutor.contains(Keys::Counter, CardFields::Keys)
                     || (tutor.contains(Restrictions::All, CardFields::Restrictions) && tutor.contains(Zones::Graveyard, CardFields::Zones) )
                     || tutor.contains(Restrictions::Whenever, CardFields::Restrictions,) && tutor.contains(Keys::Cast, CardFields::Keys))
-                    && tutor.name != String::from("Arcum Dagsson")
-                    && tutor.name != String::from("Diabolic Intent")
-                    && tutor.name != String::from("Razaketh, the Foulblooded"){
+                    // Creatures are what these sacrifice, not what they find
+                    && !rules::get().matches(Category::SacrificeTutor, tutor, |_| false){
 
                         match color_restrictions(sdeck, tutor, typ) {
                             Some(mut result) => {targets.append(&mut result)},
@@ -1515,11 +1509,8 @@ pub mod tutor {
                     && !tutor.contains(Restrictions::Control, CardFields::Restrictions) 
                     && !tutor.contains(Keys::Investigate, CardFields::Keys) )
                 || tutor.contains(CardType::Planeswalker, CardFields::OracleType) )
-                // Very special tutor. Got a lot of text and can't be passed through existing rules
-                || tutor.name == String::from("Scrapyard Recombiner")
-                // Falls somewhere through a rule, TODO: make it passing above rules 
-                || tutor.name == String::from("Diabolic Intent")
-                || tutor.name == String::from("Razaketh, the Foulblooded") 
+                // Their costs name card types although they find any card
+                || rules::get().matches(Category::AnyTutor, tutor, |_| false)
                 {
                     for card in &deck.library {    
                         if tutor.contains(Keys::NonLegendary, CardFields::Keys) {
@@ -1533,7 +1524,7 @@ pub mod tutor {
                             }
                         }else { 
                             for sub in ArtifactSubtype::iter() {
-                                if tutor.contains(&sub, CardFields::OracleText) {
+                                if tutor.contains(sub, CardFields::OraclePhrase) {
                                    for typ in &card.cardtype {
                                     match &typ {
                                         &CardType::Artifact(artifact) => {
@@ -1556,7 +1547,7 @@ pub mod tutor {
                                 }
                             }
                             for sub in CreatureSubtype::iter() {
-                                if tutor.contains(&sub, CardFields::OracleText) && !tutor.contains(Keywords::Suspend, CardFields::Keywords){
+                                if tutor.contains(sub, CardFields::OraclePhrase) && !tutor.contains(Keywords::Suspend, CardFields::Keywords){
                                     for typ in &card.cardtype {
                                         match &typ {
                                             &CardType::Creature(creature) => {
@@ -1579,7 +1570,7 @@ pub mod tutor {
                                  }
                             }
                             for sub in EnchantmentSubtype::iter() {
-                                if tutor.contains(&sub, CardFields::OracleText) {
+                                if tutor.contains(sub, CardFields::OraclePhrase) {
                                     for typ in &card.cardtype {
                                         match &typ {
                                             &CardType::Enchantment(enchantment) => {
@@ -1602,7 +1593,7 @@ pub mod tutor {
                                  }
                             }
                             for sub in SpellSubtype::iter() {
-                                if tutor.contains(&sub, CardFields::OracleText) {
+                                if tutor.contains(sub, CardFields::OraclePhrase) {
                                     for typ in &card.cardtype {
                                          if( *typ == CardType::Instant(Some(vec![sub])) 
                                          || *typ == CardType::Sorcery(Some(vec![sub])))
@@ -1632,7 +1623,7 @@ pub mod tutor {
         // there are tutors who search keywords like flash but are not further restricted. So return restriction if found for keywords
         
         for keyword in Keywords::iter() {
-            if tutor.contains(keyword, CardFields::OracleText) {
+            if tutor.contains(keyword, CardFields::OraclePhrase) {
                 for card in &deck.library {
                     match &card.keywords {
                         Some(cardkeywords) => {
@@ -1767,7 +1758,7 @@ pub mod tutor {
             // flash is linked in the keyword for loop and then will fall through all if's and end up in else where all
             // instants will be linked to it
         } else {
-            if tutor.name != String::from("Scrapyard Recombiner") {  
+            if !rules::get().matches(Category::AnyTutor, tutor, |_| false) {
                 match cardtype {
                     CardType::Artifact(_) => {
                         for card in &sdeck.artifacts {
@@ -1805,156 +1796,20 @@ pub mod tutor {
         }
         result
     }
-    fn less<'deck>(deck: &'deck Deck, tutor: &Card, cardtype: CardType) -> Vec<&'deck Card> {
+    pub(super) fn less<'deck>(deck: &'deck Deck, tutor: &Card, cardtype: CardType) -> Vec<&'deck Card> {
         let mut result: Vec<&Card> = Vec::new();
-        match &tutor.restrictions {
-            Some(restrictions) => {
-                for restriciton in restrictions {
-                    match restriciton {
-                        /* 
-                        Restrictions::OneStr => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc < 1.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::TwoStr => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc < 2.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::ThreeStr => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc < 3.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        */
-                        Restrictions::Zero => (),
-                        Restrictions::One => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc < 1.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Two => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc < 2.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Three => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc < 3.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Four => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc < 4.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Five => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc < 5.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Six => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc < 6.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Seven => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc < 7.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Eight => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc < 8.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Nine => {for card in &deck.library {
-                            if card.name != tutor.name {
-                                if card.cmc < 9.0 && card.contains(&cardtype, CardFields::CardType){
-                                    result.push(&card);
-                                }
-                            }
-                        }},
-                        Restrictions::Ten => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc < 10.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Eleven => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc < 11.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Twelve => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc < 12.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        _ => (),
-                    }
-                }
-            },
-            None => (),
+
+        // Every number in the tutor text is a mana value bound, "less than 4" finds cards below 4
+        for bound in tutor.restrictions.iter().flatten().filter_map(number) {
+            let target = Query::cmc_lt(bound) & Query::cardtype(cardtype.clone()) & !Query::name(&tutor.name);
+            result.extend(deck.library.iter().filter(|card| target.matches(card)));
         }
         result
     }
+    // Restrictions::One to Restrictions::Twelve, zero bounds nothing
+    fn number(restriction: &Restrictions) -> Option<f32> {
+        restriction.to_string().parse::<f32>().ok().filter(|bound| *bound > 0.0)
+    }
     fn equal<'deck>(deck: &'deck Deck, tutor: &Card, cardtype: CardType) -> Vec<&'deck Card>{
         let mut result: Vec<&Card> = Vec::new();
         match &tutor.restrictions {
@@ -2113,161 +1968,19 @@ pub mod tutor {
         }
         result
     }
-    fn less_or_equal<'deck>(deck: &'deck Deck, tutor: &Card, cardtype: CardType) -> Vec<&'deck Card> {
+    pub(super) fn less_or_equal<'deck>(deck: &'deck Deck, tutor: &Card, cardtype: CardType) -> Vec<&'deck Card> {
         let mut result: Vec<&Card> = Vec::new();
-        match &tutor.restrictions {
-            Some(restrictions) => {
-                for restriciton in restrictions {
-                    match restriciton {
-                        /*
-                        Restrictions::OneStr => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc <= 1.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::TwoStr => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc <= 2.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::ThreeStr => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc <= 3.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        */
-                        Restrictions::Zero => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc == 0.0 {
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::One => {
-                            for card in &deck.library {
-                                if card.name != tutor.name && card.contains(&cardtype, CardFields::CardType){
-                                    if card.cmc <= 1.0 {
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Two => {
-                            for card in &deck.library {
-                                if card.name != tutor.name && card.contains(&cardtype, CardFields::CardType){
-                                    if card.cmc <= 2.0 {
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Three => {
-                            for card in &deck.library {
-                                if card.name != tutor.name && card.contains(&cardtype, CardFields::CardType){
-                                    if card.cmc <= 3.0 {
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Four => {
-                            for card in &deck.library {
-                                if card.name != tutor.name && card.contains(&cardtype, CardFields::CardType){
-                                    if card.cmc <= 4.0 {
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Five => {
-                            for card in &deck.library {
-                                if card.name != tutor.name && card.contains(&cardtype, CardFields::CardType){
-                                    if card.cmc <= 5.0 {
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Six => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc <= 6.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Seven => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc <= 7.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Eight => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc <= 8.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Nine => {for card in &deck.library {
-                            if card.name != tutor.name {
-                                if card.cmc <= 9.0 && card.contains(&cardtype, CardFields::CardType){
-                                    result.push(&card);
-                                }
-                            }
-                        }},
-                        Restrictions::Ten => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc <= 10.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Eleven => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc <= 11.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        Restrictions::Twelve => {
-                            for card in &deck.library {
-                                if card.name != tutor.name {
-                                    if card.cmc <= 12.0 && card.contains(&cardtype, CardFields::CardType){
-                                        result.push(&card);
-                                    }
-                                }
-                            }
-                        },
-                        _ => (),
-                    }
-                }
-            },
-            None => (),
+
+        for restriction in tutor.restrictions.iter().flatten() {
+            // Mana value 0 finds any card type
+            let target = match restriction {
+                Restrictions::Zero => Query::cmc(Compare::Equal, 0.0),
+                _ => match number(restriction) {
+                    Some(bound) => Query::cmc_le(bound) & Query::cardtype(cardtype.clone()),
+                    None => continue,
+                },
+            } & !Query::name(&tutor.name);
+            result.extend(deck.library.iter().filter(|card| target.matches(card)));
         }
         result
     }
@@ -2289,45 +2002,18 @@ pub mod tutor {
         match &tutor.keys {
             Some(keys) => {
                 for key in keys {
-                    match key {
-                        Keys::SWhite => {
-                            for card in sorted_deck {
-                                if card.name != tutor.name && card.contains(Colors::White, CardFields::ManaCost) {
-                                    targets.push(*card)
-                                }
-                            }
-                        },
-                        Keys::SBlue => {
-                            for card in sorted_deck {
-                                if card.name != tutor.name && card.contains(Colors::Blue, CardFields::ManaCost) {
-                                    targets.push(*card)
-                                }
-                            }
-                        },
-                        Keys::SBlack => {
-                            for card in sorted_deck  {
-                                if card.name != tutor.name && card.contains(Colors::Black, CardFields::ManaCost) {
-                                    targets.push(*card)
-                                }
-                            }
-                        },
-                        Keys::SRed => {
-                            for card in sorted_deck  {
-                                if card.name != tutor.name && card.contains(Colors::Red, CardFields::ManaCost) {
-                                    targets.push(*card)
-                                }
-                            }
-                        },
-                        // Dryad arbor is a Creatur - Forest Dryad, therefore it's green but not mentioned on the card.
-                        Keys::SGreen => {
-                            for card in sorted_deck  {
-                                if (card.name != tutor.name && card.contains(Colors::Green, CardFields::ManaCost) )
-                                || card.name == String::from("Dryad Arbor"){
-                                    targets.push(*card)
-                                }
-                            }
-                        },
-                        _ => (),
+                    let color = match key {
+                        Keys::SWhite => Colors::White,
+                        Keys::SBlue => Colors::Blue,
+                        Keys::SBlack => Colors::Black,
+                        Keys::SRed => Colors::Red,
+                        Keys::SGreen => Colors::Green,
+                        _ => continue,
+                    };
+                    for card in sorted_deck {
+                        if card.name != tutor.name && card.colors.contains(&color) && !targets.contains(card) {
+                            targets.push(*card)
+                        }
                     }
                 }
             },
@@ -2340,12 +2026,1212 @@ pub mod tutor {
         }
     }
 }
+/****************************************** Draw Probability *************************************************/
+pub mod probability {
+    use crate::types::{Card, Deck, CEerror, CEResult};
+    use crate::import::combo::ComboResult;
+    use super::tutor::Tutor;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Play {
+        OnThePlay,
+        OnTheDraw,
+    }
+
+    impl Play {
+        // Opening hand of seven plus one card per turn, the player on the play skips the first draw
+        pub fn cards_seen(&self, turn: u8) -> usize {
+            match self {
+                Play::OnThePlay => 7 + turn as usize - 1,
+                Play::OnTheDraw => 7 + turn as usize,
+            }
+        }
+    }
+
+    // Chance to find at least k successes with draws cards out of population cards containing successes hits
+    pub fn hypergeometric(population: usize, successes: usize, draws: usize, k: usize) -> CEResult<f64> {
+        if successes > population || draws > population || k > draws {
+            return Err(CEerror::HyperGeoFailed);
+        }
+        if k == 0 {
+            return Ok(1.0);
+        }
+
+        let total = choose(population, draws);
+        let mut probability = 0.0;
+
+        for i in k..=successes.min(draws) {
+            if draws - i <= population - successes {
+                probability += choose(successes, i) * choose(population - successes, draws - i) / total;
+            }
+        }
+
+        Ok(probability.min(1.0))
+    }
+    // Chance to draw at least k cards of a group (e.g. Effect::draw or ManaDist::dorks) by the given turn. The commander
+    // sits in the command zone, so only the library is the population.
+    pub fn at_least(deck: &Deck, group: &[&Card], k: usize, turn: u8, play: Play) -> CEResult<f64> {
+        if turn == 0 {
+            return Err(CEerror::HyperGeoFailed);
+        }
+
+        let population = deck.library.len();
+        let successes = in_library(deck, group);
+        let draws = play.cards_seen(turn);
+
+        if draws > population {
+            return Err(CEerror::HyperGeoFailed);
+        }
+
+        hypergeometric(population, successes, draws, k)
+    }
+    // Library cards belonging to one combo, pieces in the command zone are not part of the population
+    pub fn combo_pieces<'deck>(deck: &'deck Deck, combo: &ComboResult) -> Vec<&'deck Card> {
+        let mut pieces = Vec::<&Card>::new();
+
+        for piece in combo.pieces() {
+            for card in &deck.library {
+                if &card.name == piece {
+                    pieces.push(card);
+                }
+            }
+        }
+        pieces
+    }
+    /* Chance to hold every combo piece by the given turn, where a tutor counts as an extra copy of each piece it can find
+       and pieces in the command zone are always available. Inclusion-exclusion over the pieces:
+       P(all) = sum over subsets S of (-1)^|S| * P(no finder of any piece in S drawn).
+       One tutor fetching two pieces at once is not excluded, so this is an upper estimate for combos sharing tutors. */
+    pub fn assemble(deck: &Deck, combo: &ComboResult, tutors: &Tutor, turn: u8, play: Play) -> CEResult<f64> {
+        if turn == 0 || !combo.is_complete() {
+            return Err(CEerror::HyperGeoFailed);
+        }
+
+        let population = deck.library.len();
+        let draws = play.cards_seen(turn);
+        if draws > population {
+            return Err(CEerror::HyperGeoFailed);
+        }
+
+        // Library positions of every card that finds a piece, one set per piece outside the command zone
+        let mut finders = Vec::<Vec<usize>>::new();
+        for piece in combo.pieces() {
+            // A complete combo has every piece outside the library in the command zone
+            if combo.commander_combo_piece() && !deck.library.iter().any(|card| &card.name == piece) {
+                continue;
+            }
+            let tutor_names: Vec<&String> = tutors.tutor.iter()
+                .filter(|(_, targets)| targets.iter().any(|target| &target.name == piece))
+                .map(|(tutor, _)| *tutor)
+                .collect();
+            let mut found = Vec::<usize>::new();
+
+            for (position, card) in deck.library.iter().enumerate() {
+                if &card.name == piece || tutor_names.iter().any(|name| **name == card.name) {
+                    found.push(position);
+                }
+            }
+            finders.push(found);
+        }
+
+        let total = choose(population, draws);
+        let mut probability = 0.0;
+
+        for subset in 0..(1usize << finders.len()) {
+            let mut union = Vec::<usize>::new();
+            for (i, found) in finders.iter().enumerate() {
+                if subset & (1 << i) != 0 {
+                    for position in found {
+                        if !union.contains(position) {
+                            union.push(*position);
+                        }
+                    }
+                }
+            }
+            let none_drawn = choose(population - union.len(), draws) / total;
+
+            if subset.count_ones().is_multiple_of(2) {
+                probability += none_drawn;
+            } else {
+                probability -= none_drawn;
+            }
+        }
+
+        Ok(probability.clamp(0.0, 1.0))
+    }
+    // Groups built by basic can hold backsides of a card, which count as the library card they are printed on
+    fn in_library(deck: &Deck, group: &[&Card]) -> usize {
+        let mut hits = 0;
+
+        for card in &deck.library {
+            let mut found = false;
+            for member in group {
+                if member.name == card.name {
+                    found = true;
+                }
+                if let Some(backside) = &card.backside {
+                    if member.name == backside.name {
+                        found = true;
+                    }
+                }
+            }
+            if found {
+                hits += 1;
+            }
+        }
+        hits
+    }
+    pub(super) fn choose(n: usize, k: usize) -> f64 {
+        if k > n {
+            return 0.0;
+        }
+        let k = k.min(n - k);
+        let mut result = 1.0;
+
+        for i in 0..k {
+            result = result * (n - i) as f64 / (i + 1) as f64;
+        }
+        result
+    }
+}
 /****************************************** Eval Powerlevel **************************************************/
 pub mod powerlevel {
-    #[allow(dead_code, unused_variables)]
+    use super::basic::Basic;
+    use super::tutor::Tutor;
+
+    #[derive(Debug)]
+    pub struct Factor {
+        pub name: &'static str,
+        pub value: f32,
+        pub points: f32,
+        pub max_points: f32,
+    }
+
+    #[derive(Debug)]
     pub struct Powerlevel {
-        score: u8,
+        pub score: u8,
+        pub factors: Vec<Factor>,
     }
     
-    impl Powerlevel {}
-}
\ No newline at end of file
+    impl Powerlevel {
+        // Every factor adds up to its max_points, all factors together add up to 9 on top of a base score of 1
+        pub fn new(basics: &Basic, tutors: &Tutor) -> Powerlevel {
+            let mut factors = Vec::<Factor>::new();
+
+            let fastmana = basics.effect.fastmana.len() as f32;
+            factors.push(Factor::scale("Fast mana", fastmana, 6.0, 2.0));
+
+            let tutor = tutors.tutor.len() as f32;
+            factors.push(Factor::scale("Tutors", tutor, 8.0, 2.0));
+
+            let combos = basics.combo.len() as f32;
+            factors.push(Factor::scale("Combos", combos, 3.0, 1.0));
+
+            // Two card combos are easier to assemble and protect than anything with more pieces
+            let mut fewest_pieces = 0;
+            for combo in &basics.combo {
+                if fewest_pieces == 0 || combo.num_pieces() < fewest_pieces {
+                    fewest_pieces = combo.num_pieces();
+                }
+            }
+            let piece_points = match fewest_pieces {
+                0 => 0.0,
+                1 | 2 => 1.0,
+                3 => 0.5,
+                _ => 0.25,
+            };
+            factors.push(Factor { name: "Combo pieces", value: fewest_pieces as f32, points: piece_points, max_points: 1.0 });
+
+            // Average CMC of 2 or lower is full points, 4 or higher is none
+            let average = average_cmc(basics);
+            let cmc_points = (4.0 - average).clamp(0.0, 2.0) / 2.0 * 1.5;
+            factors.push(Factor { name: "Average CMC", value: average, points: cmc_points, max_points: 1.5 });
+
+            let stax = basics.effect.stax.len() as f32;
+            factors.push(Factor::scale("Stax", stax, 6.0, 0.75));
+
+            let interaction = (basics.effect.removal.len() 
+                + basics.effect.counter.len() 
+                + basics.effect.boardwipe.len() 
+                + basics.effect.bounce.len()) as f32;
+            factors.push(Factor::scale("Interaction", interaction, 15.0, 0.75));
+
+            let mut total = 1.0;
+            for factor in &factors {
+                total += factor.points;
+            }
+
+            Powerlevel {
+                score: (total.round() as u8).clamp(1, 10),
+                factors,
+            }
+        }
+    }
+
+    impl Factor {
+        // Linear share of max_points, capped once value reaches saturation
+        fn scale(name: &'static str, value: f32, saturation: f32, max_points: f32) -> Factor {
+            Factor {
+                name,
+                value,
+                points: value.min(saturation) / saturation * max_points,
+                max_points,
+            }
+        }
+    }
+
+    fn average_cmc(basics: &Basic) -> f32 {
+        let mut sum = 0.0;
+        let mut num = 0;
+
+        for (cmc, cards) in &basics.mana_cost {
+            sum += *cmc as f32 * cards.len() as f32;
+            num += cards.len();
+        }
+
+        if num == 0 {
+            return 0.0;
+        }
+        sum / num as f32
+    }
+}
+/****************************************** Goldfish Simulation **********************************************/
+pub mod goldfish {
+    use std::collections::{BTreeMap, HashMap};
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+    use serde::Serialize;
+    use crate::types::{Card, Deck, ManaCost, ManaSymbol, CEerror, CEResult};
+    use crate::logic::oracle;
+    use super::basic::Basic;
+    use super::tutor::Tutor;
+    use super::probability::Play;
+
+    /* When a seven card hand is kept, the first mulligan is free in commander. Hands have to be inside the land range and
+       hold at least the given number of ramp pieces and tutors, after max_mulligans the simulation keeps any hand */
+    #[derive(Debug, Clone, Serialize)]
+    pub struct KeepRule {
+        pub min_lands: usize,
+        pub max_lands: usize,
+        pub ramp: usize,
+        pub tutor: usize,
+        pub max_mulligans: usize,
+    }
+
+    impl KeepRule {
+        /* "never" keeps every seven, "2-5" keeps hands with two to five lands. Longer rules list comma separated terms:
+           "lands=3-4,ramp,tutor,mulligans=2", where ramp and tutor take an optional minimum like ramp=2 */
+        pub fn parse(input: &str) -> CEResult<KeepRule> {
+            let mut rule = KeepRule { min_lands: 0, max_lands: 7, ramp: 0, tutor: 0, max_mulligans: 3 };
+            let invalid = || CEerror::ConfigError(format!("Mulligan rule {} can not be read, try never, 2-5 or lands=2-5,ramp,tutor", input));
+
+            if input == "never" {
+                rule.max_mulligans = 0;
+                return Ok(rule);
+            }
+
+            for term in input.split(',').map(str::trim) {
+                let (key, value) = match term.split_once('=') {
+                    Some((key, value)) => (key.trim(), Some(value.trim())),
+                    None if term.contains('-') => ("lands", Some(term)),
+                    None => (term, None),
+                };
+
+                match (key, value) {
+                    ("lands", Some(range)) => {
+                        let (min, max) = range.split_once('-').ok_or_else(invalid)?;
+                        rule.min_lands = min.parse().map_err(|_| invalid())?;
+                        rule.max_lands = max.parse().map_err(|_| invalid())?;
+                        if rule.min_lands > rule.max_lands {
+                            return Err(invalid());
+                        }
+                    },
+                    ("ramp", None) => rule.ramp = 1,
+                    ("ramp", Some(t)) => rule.ramp = t.parse().map_err(|_| invalid())?,
+                    ("tutor", None) => rule.tutor = 1,
+                    ("tutor", Some(t)) => rule.tutor = t.parse().map_err(|_| invalid())?,
+                    ("mulligans", Some(t)) => rule.max_mulligans = t.parse().map_err(|_| invalid())?,
+                    _ => return Err(invalid()),
+                }
+            }
+            Ok(rule)
+        }
+        // Only the hand criteria, the forced keep after max_mulligans is up to the simulation
+        pub fn keeps(&self, hand: &[&Card], roles: &Roles) -> bool {
+            let lands = hand.iter().filter(|card| roles.is_land(card)).count();
+            let ramp = hand.iter().filter(|card| roles.is_ramp(card)).count();
+            let tutor = hand.iter().filter(|card| roles.is_tutor(card)).count();
+
+            lands >= self.min_lands && lands <= self.max_lands && ramp >= self.ramp && tutor >= self.tutor
+        }
+        pub fn describe(&self) -> String {
+            let mut terms = vec![format!("lands={}-{}", self.min_lands, self.max_lands)];
+
+            if self.ramp > 0 {
+                terms.push(format!("ramp={}", self.ramp));
+            }
+            if self.tutor > 0 {
+                terms.push(format!("tutor={}", self.tutor));
+            }
+            terms.push(format!("mulligans={}", self.max_mulligans));
+            terms.join(",")
+        }
+    }
+
+    // What the simulation does with a card, taken from the classification in statistic::basic and statistic::tutor
+    #[derive(Debug, Default)]
+    pub struct Roles {
+        lands: Vec<String>,
+        // Mana rocks, dorks, mana enchantments and permanent fast mana with the mana they add
+        producers: HashMap<String, usize>,
+        dorks: Vec<String>,
+        // Producers, rituals and land ramp
+        ramp: Vec<String>,
+        tutors: Vec<String>,
+    }
+
+    impl Roles {
+        pub fn new(basics: &Basic) -> Roles {
+            let mut roles = Roles {
+                lands: basics.cardtype.lands.iter().map(|card| card.name.to_string()).collect(),
+                producers: HashMap::new(),
+                dorks: basics.mana_dist.dorks.iter().map(|card| card.name.to_string()).collect(),
+                ramp: Vec::new(),
+                tutors: Vec::new(),
+            };
+
+            for card in basics.mana_dist.dorks.iter()
+                .chain(&basics.mana_dist.artifacts)
+                .chain(&basics.mana_dist.enchantments)
+                .chain(&basics.effect.fastmana) {
+                if roles.lands.contains(&card.name) {
+                    continue;
+                }
+                roles.ramp.push(card.name.to_string());
+                // Rituals are gone after one use
+                if is_permanent(card) {
+                    roles.producers.insert(card.name.to_string(), production(card));
+                }
+            }
+            roles
+        }
+        pub fn tutors(mut self, tutor: &Tutor) -> Roles {
+            self.tutors.extend(tutor.tutor.keys().map(|name| name.to_string()));
+            self.ramp.extend(tutor.landramp.keys().map(|name| name.to_string()));
+            self
+        }
+        pub fn is_land(&self, card: &Card) -> bool {
+            self.lands.contains(&card.name)
+        }
+        pub fn is_ramp(&self, card: &Card) -> bool {
+            self.ramp.contains(&card.name)
+        }
+        pub fn is_tutor(&self, card: &Card) -> bool {
+            self.tutors.contains(&card.name)
+        }
+        fn production(&self, card: &Card) -> Option<usize> {
+            self.producers.get(&card.name).copied()
+        }
+    }
+
+    // Counts per value, e.g. turn the commander was cast to number of games
+    pub type Distribution = BTreeMap<usize, usize>;
+
+    #[derive(Debug, Serialize)]
+    pub struct Goldfish {
+        pub games: usize,
+        pub turns: u8,
+        pub play: String,
+        pub rule: KeepRule,
+        pub mulligans: Distribution,
+        // Turn the first commander hit the battlefield, games without a cast are left out
+        pub commander_turn: Distribution,
+        pub commander_not_cast: usize,
+        // Index 0 is turn one
+        pub mana: Vec<Distribution>,
+        pub hand: Vec<Distribution>,
+    }
+
+    /* Plays the opening turns without an opponent. Every turn: draw, play a land, then cast mana producers cheapest first
+       and the commander as soon as the mana is there. Colors are not tracked, every land taps for one mana and
+       dorks can tap the turn after they were cast. */
+    pub fn simulate(deck: &Deck, roles: &Roles, games: usize, turns: u8, play: Play, rule: &KeepRule, seed: Option<u64>) -> CEResult<Goldfish> {
+        if turns == 0 {
+            return Err(CEerror::SimulationError(String::from("Simulate at least one turn")));
+        }
+        if play.cards_seen(turns) > deck.library.len() {
+            return Err(CEerror::SimulationError(format!("{} cards can not be drawn for {} turns", deck.library.len(), turns)));
+        }
+
+        let mut rng = rng(seed);
+        let commander_cost = deck.commander.iter().map(|card| card.cmc as usize).min();
+
+        let mut result = Goldfish {
+            games,
+            turns,
+            play: format!("{:?}", play),
+            rule: rule.clone(),
+            mulligans: Distribution::new(),
+            commander_turn: Distribution::new(),
+            commander_not_cast: 0,
+            mana: vec![Distribution::new(); turns as usize],
+            hand: vec![Distribution::new(); turns as usize],
+        };
+
+        for _game in 0..games {
+            let mut library: Vec<&Card> = deck.library.iter().collect();
+            let (mulligans, mut hand) = opening_hand(&mut library, roles, rule, &mut rng);
+            *result.mulligans.entry(mulligans).or_insert(0) += 1;
+
+            let mut lands = 0;
+            let mut producers = Vec::<(usize, u8)>::new();
+            let mut commander_cast = None;
+
+            for turn in 1..=turns {
+                if turn > 1 || play == Play::OnTheDraw {
+                    if let Some(card) = library.pop() {
+                        hand.push(card);
+                    }
+                }
+
+                if let Some(position) = hand.iter().position(|card| roles.is_land(card)) {
+                    hand.remove(position);
+                    lands += 1;
+                }
+
+                // Dorks have summoning sickness, everything else taps right away
+                let mut mana = lands + producers.iter().filter(|(_, cast)| *cast < turn).map(|(amount, _)| amount).sum::<usize>();
+                cast_commander(&mut commander_cast, commander_cost, &mut mana, turn);
+
+                loop {
+                    let cheapest = hand.iter().enumerate()
+                        .filter(|(_, card)| roles.production(card).is_some() && card.cmc as usize <= mana)
+                        .min_by_key(|(_, card)| card.cmc as usize)
+                        .map(|(position, _)| position);
+
+                    match cheapest {
+                        Some(position) => {
+                            let card = hand.remove(position);
+                            mana -= card.cmc as usize;
+                            let amount = roles.production(card).unwrap_or(0);
+                            if roles.dorks.contains(&card.name) {
+                                producers.push((amount, turn));
+                            } else {
+                                producers.push((amount, 0));
+                                mana += amount;
+                            }
+                        },
+                        None => break,
+                    }
+                }
+
+                let available = lands + producers.iter().filter(|(_, cast)| *cast < turn).map(|(amount, _)| amount).sum::<usize>();
+                *result.mana[turn as usize - 1].entry(available).or_insert(0) += 1;
+
+                cast_commander(&mut commander_cast, commander_cost, &mut mana, turn);
+                *result.hand[turn as usize - 1].entry(hand.len()).or_insert(0) += 1;
+            }
+
+            match commander_cast {
+                Some(turn) => *result.commander_turn.entry(turn as usize).or_insert(0) += 1,
+                None => result.commander_not_cast += 1,
+            }
+        }
+        Ok(result)
+    }
+    #[derive(Debug, Serialize)]
+    pub struct Evaluation {
+        pub rule: String,
+        pub hands: usize,
+        // Share of first seven card hands the rule keeps
+        pub keep_rate: f64,
+        pub mulligans: Distribution,
+        pub expected_mulligans: f64,
+        pub kept: Vec<Vec<String>>,
+        pub mulliganed: Vec<Vec<String>>,
+    }
+
+    // Every rule sees the same shuffles, so differences come from the rules and not from the dice
+    pub fn evaluate(deck: &Deck, roles: &Roles, rules: &[KeepRule], hands: usize, examples: usize, seed: Option<u64>) -> CEResult<Vec<Evaluation>> {
+        if deck.library.len() < 7 {
+            return Err(CEerror::SimulationError(format!("{} cards are no opening hand", deck.library.len())));
+        }
+
+        let mut seeds = rng(seed);
+        let games: Vec<u64> = (0..hands).map(|_| rand::Rng::gen(&mut seeds)).collect();
+        let mut result = Vec::<Evaluation>::new();
+
+        for rule in rules {
+            let mut evaluation = Evaluation {
+                rule: rule.describe(),
+                hands,
+                keep_rate: 0.0,
+                mulligans: Distribution::new(),
+                expected_mulligans: 0.0,
+                kept: Vec::new(),
+                mulliganed: Vec::new(),
+            };
+            let mut kept_seven = 0;
+
+            for game in &games {
+                let mut rng = StdRng::seed_from_u64(*game);
+                let mut library: Vec<&Card> = deck.library.iter().collect();
+                library.shuffle(&mut rng);
+
+                let seven = &library[library.len() - 7..];
+                let names: Vec<String> = seven.iter().map(|card| card.name.to_string()).collect();
+                if rule.keeps(seven, roles) {
+                    kept_seven += 1;
+                    if evaluation.kept.len() < examples {
+                        evaluation.kept.push(names);
+                    }
+                } else if evaluation.mulliganed.len() < examples {
+                    evaluation.mulliganed.push(names);
+                }
+
+                let mut library: Vec<&Card> = deck.library.iter().collect();
+                let mut rng = StdRng::seed_from_u64(*game);
+                let (mulligans, _) = opening_hand(&mut library, roles, rule, &mut rng);
+                *evaluation.mulligans.entry(mulligans).or_insert(0) += 1;
+            }
+
+            evaluation.keep_rate = kept_seven as f64 / hands.max(1) as f64;
+            evaluation.expected_mulligans = mean(&evaluation.mulligans);
+            result.push(evaluation);
+        }
+        Ok(result)
+    }
+    fn rng(seed: Option<u64>) -> StdRng {
+        match seed {
+            Some(t) => StdRng::seed_from_u64(t),
+            None => StdRng::from_entropy(),
+        }
+    }
+    fn cast_commander(cast: &mut Option<u8>, cost: Option<usize>, mana: &mut usize, turn: u8) {
+        if let Some(cost) = cost {
+            if cast.is_none() && *mana >= cost {
+                *mana -= cost;
+                *cast = Some(turn);
+            }
+        }
+    }
+    pub fn mean(distribution: &Distribution) -> f64 {
+        let games: usize = distribution.values().sum();
+        if games == 0 {
+            return 0.0;
+        }
+        distribution.iter().map(|(value, count)| (value * count) as f64).sum::<f64>() / games as f64
+    }
+    // London mulligan: draw seven, put one card on the bottom per mulligan after the free first one
+    fn opening_hand<'deck>(library: &mut Vec<&'deck Card>, roles: &Roles, rule: &KeepRule, rng: &mut StdRng) -> (usize, Vec<&'deck Card>) {
+        let mut mulligans = 0;
+
+        loop {
+            library.shuffle(rng);
+            let mut hand = library.split_off(library.len() - 7);
+
+            if mulligans >= rule.max_mulligans || rule.keeps(&hand, roles) {
+                for _ in 0..mulligans.saturating_sub(1) {
+                    let card = bottom(&mut hand, roles, rule);
+                    library.insert(0, card);
+                }
+                return (mulligans, hand);
+            }
+            library.extend(hand);
+            mulligans += 1;
+        }
+    }
+    // Excess lands go first, otherwise the most expensive spell
+    fn bottom<'deck>(hand: &mut Vec<&'deck Card>, roles: &Roles, rule: &KeepRule) -> &'deck Card {
+        let lands = hand.iter().filter(|card| roles.is_land(card)).count();
+
+        let position = if lands > rule.max_lands.min(4) {
+            hand.iter().position(|card| roles.is_land(card))
+        } else {
+            hand.iter().enumerate()
+                .filter(|(_, card)| !roles.is_land(card))
+                .max_by(|(_, a), (_, b)| a.cmc.total_cmp(&b.cmc))
+                .map(|(position, _)| position)
+        };
+        hand.remove(position.unwrap_or(0))
+    }
+    fn is_permanent(card: &Card) -> bool {
+        !card.cardtype.iter().any(|cardtype| matches!(cardtype, crate::types::CardType::Instant(_) | crate::types::CardType::Sorcery(_)))
+    }
+    /* Mana of the best "Add" ability less the mana its cost pays, a Signet nets one. Choices like
+       "Add {G}, {W}, or {U}" are one mana, cards adding "one mana of any color" count as one */
+    pub fn production(card: &Card) -> usize {
+        let mut best = None;
+
+        for line in oracle::unescape(&card.oracle_text).lines() {
+            let (cost, effect) = match line.split_once("Add ") {
+                Some((before, after)) => (before.split_once(':').map(|(cost, _)| cost).unwrap_or(""), after),
+                None => continue,
+            };
+            let effect = effect.split('.').next().unwrap_or("");
+
+            let gross = effect.split(',')
+                .flat_map(|choice| choice.split(" or "))
+                .map(|choice| ManaCost::parse(choice).symbols.len())
+                .max()
+                .unwrap_or(0)
+                .max(1);
+            let paid = ManaCost::parse(cost).symbols.iter()
+                .filter(|symbol| !matches!(symbol, ManaSymbol::Other(_)))
+                .map(ManaSymbol::mana_value)
+                .sum::<f32>() as usize;
+
+            best = best.max(Some(gross.saturating_sub(paid)));
+        }
+        best.unwrap_or(1)
+    }
+}
+/****************************************** Mana Base Recommendation *****************************************/
+pub mod manabase {
+    use crate::types::{AbilityKind, Card, CardFields, CardType, Colors, Deck, Keys, LandSubtype, CEResult};
+    use super::basic::Basic;
+    use super::probability::{self, Play};
+
+    // Chance to have the colored sources for a spell on curve a deck should reach
+    pub const TARGET: f64 = 0.9;
+    // Mana of a color the card does not print, Birds of Paradise or Command Tower
+    const ANY_COLOR: [&str; 3] = ["any color", "any one color", "any combination of colors"];
+
+    #[derive(Debug)]
+    pub struct LandAdvice {
+        pub current: usize,
+        pub recommended: usize,
+        pub average_mv: f64,
+        pub cheap_ramp_draw: usize,
+    }
+
+    #[derive(Debug)]
+    pub struct ColorAdvice<'deck> {
+        pub color: Colors,
+        pub pips: usize,
+        // Spell with the hardest requirement, most pips for the lowest mana value
+        pub hardest: Option<&'deck Card>,
+        pub required: usize,
+        pub current: usize,
+    }
+
+    impl ColorAdvice<'_> {
+        pub fn under_supplied(&self) -> bool {
+            self.current < self.required
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Manabase<'deck> {
+        pub target: f64,
+        pub lands: LandAdvice,
+        pub colors: Vec<ColorAdvice<'deck>>,
+    }
+
+    impl <'deck> Manabase<'deck> {
+        pub fn new(deck: &'deck Deck, basics: &Basic, play: Play, target: f64) -> Manabase<'deck> {
+            let mut colors = Vec::<ColorAdvice>::new();
+
+            for color in Colors::identity() {
+                let total: usize = deck.library.iter().chain(&deck.commander).map(|card| pips(card, color)).sum();
+                if total == 0 {
+                    continue;
+                }
+
+                let mut hardest = None;
+                let mut required = 0;
+                for card in deck.library.iter().chain(&deck.commander) {
+                    let needed = sources(deck, pips(card, color), card.cmc.max(1.0) as u8, play, target).unwrap_or(0);
+                    if needed > required {
+                        required = needed;
+                        hardest = Some(card);
+                    }
+                }
+
+                colors.push(ColorAdvice {
+                    color,
+                    pips: total,
+                    hardest,
+                    required,
+                    current: deck.library.iter().filter(|card| produces(card, deck, basics, color)).count(),
+                });
+            }
+
+            Manabase { target, lands: lands(deck, basics), colors }
+        }
+    }
+
+    /* Frank Karsten's regression for 99 card decks: 31.42 + 3.13 * average mana value - 0.28 * cheap ramp and draw.
+       The average is over nonland cards without the commander, cheap means mana value two or less */
+    fn lands(deck: &Deck, basics: &Basic) -> LandAdvice {
+        let spells: Vec<&Card> = deck.library.iter().filter(|card| !is_land(card)).collect();
+        let average_mv = if spells.is_empty() {
+            0.0
+        } else {
+            spells.iter().map(|card| card.cmc as f64).sum::<f64>() / spells.len() as f64
+        };
+
+        let mut cheap = Vec::<&String>::new();
+        for card in basics.mana_dist.dorks.iter()
+            .chain(&basics.mana_dist.artifacts)
+            .chain(&basics.effect.fastmana)
+            .chain(&basics.effect.draw) {
+            if card.cmc <= 2.0 && !cheap.contains(&&card.name) && !is_land(card) {
+                cheap.push(&card.name);
+            }
+        }
+
+        let recommended = 31.42 + 3.13 * average_mv - 0.28 * cheap.len() as f64;
+
+        LandAdvice {
+            current: deck.library.iter().filter(|card| is_land(card)).count(),
+            recommended: recommended.round().max(0.0) as usize,
+            average_mv,
+            cheap_ramp_draw: cheap.len(),
+        }
+    }
+    // Fewest sources so that pips of them are among the cards seen by the turn the spell costs
+    fn sources(deck: &Deck, pips: usize, turn: u8, play: Play, target: f64) -> CEResult<usize> {
+        if pips == 0 {
+            return Ok(0);
+        }
+        let population = deck.library.len();
+        let draws = play.cards_seen(turn).min(population);
+
+        for sources in pips..=population {
+            if probability::hypergeometric(population, sources, draws, pips)? >= target {
+                return Ok(sources);
+            }
+        }
+        Ok(population)
+    }
+    // Only pure colored symbols count, hybrid and phyrexian costs can be paid otherwise
+    fn pips(card: &Card, color: Colors) -> usize {
+        card.mana_cost.required_pips(color) as usize
+    }
+    /* Lands and mana abilities printing the color or any color. Fetchlands count for the basic land types they find,
+       "basic land" ones for every color the deck has a basic land type of */
+    pub(super) fn produces(card: &Card, deck: &Deck, basics: &Basic, color: Colors) -> bool {
+        let producer = is_land(card)
+            || basics.mana_dist.dorks.iter()
+                .chain(&basics.mana_dist.artifacts)
+                .chain(&basics.mana_dist.enchantments)
+                .any(|source| source.name == card.name)
+            || card.abilities.iter().any(|ability| ability.kind == AbilityKind::Activated && ability.keys.contains(&Keys::Add));
+        if !producer {
+            return false;
+        }
+        if card.oracle_text.contains(&color.to_string()) || ANY_COLOR.iter().any(|phrase| card.contains(phrase, CardFields::OraclePhrase)) {
+            return true;
+        }
+
+        let land_type = match basic_land_type(color) {
+            Some(t) => t,
+            None => return false,
+        };
+        is_land(card) && card.contains(Keys::Search, CardFields::Keys)
+            && (card.contains(land_type.to_string(), CardFields::OraclePhrase)
+                || (card.contains("basic land", CardFields::OraclePhrase) && deck.library.iter().any(|land| has_land_type(land, &land_type))))
+    }
+    fn basic_land_type(color: Colors) -> Option<LandSubtype> {
+        match color {
+            Colors::White => Some(LandSubtype::Plains),
+            Colors::Blue => Some(LandSubtype::Island),
+            Colors::Black => Some(LandSubtype::Swamp),
+            Colors::Red => Some(LandSubtype::Mountain),
+            Colors::Green => Some(LandSubtype::Forest),
+            _ => None,
+        }
+    }
+    fn has_land_type(card: &Card, land_type: &LandSubtype) -> bool {
+        card.cardtype.iter().any(|cardtype| matches!(cardtype, CardType::Land(Some(subtypes)) if subtypes.contains(land_type)))
+    }
+    fn is_land(card: &Card) -> bool {
+        card.contains(CardType::Land(None), CardFields::CardType)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs, path::PathBuf};
+    use crate::types::{Card, CardFields, CardType, Colors, Deck, Keys, Restrictions, CEerror};
+    use super::basic::{self, Basic};
+    use super::tutor;
+    use super::manabase;
+    use super::goldfish::{self, KeepRule, Roles};
+    use super::probability::{self, Play};
+    use crate::import::combo::{Combo, ComboResult};
+    use super::powerlevel::Powerlevel;
+    use super::tutor::Tutor;
+
+    fn fixture(name: &str) -> Card {
+        card(name, false)
+    }
+    fn card(name: &str, commander: bool) -> Card {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(format!("{}.json", name));
+        Card::make(&fs::read_to_string(path).unwrap(), commander).unwrap()
+    }
+    // Everything but the combo search, which needs combo data on disk
+    fn basics(deck: &Deck) -> Basic {
+        Basic {
+            cardtype: basic::cardtype(deck),
+            mana_cost: basic::mana_cost(deck),
+            mana_dist: basic::mana_distribution(deck),
+            effect: basic::effect(deck),
+            combo: Vec::new(),
+            near_miss: Vec::new(),
+        }
+    }
+    fn goldfish_deck(ramp: &[&str]) -> Deck {
+        let mut library: Vec<Card> = ramp.iter().map(|name| fixture(name)).collect();
+        while library.len() < 99 {
+            library.push(fixture("forest"));
+        }
+        Deck::new(String::from("goldfish"), vec![card("titania-protector-of-argoth", true)], library)
+    }
+
+    #[test]
+    fn equipment_granting_keywords_is_protection() {
+        let library = vec![fixture("lightning-greaves"), fixture("swiftfoot-boots"), fixture("gladecover-scout"), fixture("sol-ring")];
+        let deck = Deck::new(String::from("protection"), Vec::new(), library);
+
+        let protection: Vec<&str> = basic::effect(&deck).protection.iter().map(|card| card.name.as_str()).collect();
+        assert_eq!(protection, vec!["Lightning Greaves", "Swiftfoot Boots"]);
+    }
+
+    #[test]
+    fn production_counts_choices_once_and_nets_costs() {
+        assert_eq!(goldfish::production(&fixture("sol-ring")), 2);
+        assert_eq!(goldfish::production(&fixture("noble-hierarch")), 1);
+        assert_eq!(goldfish::production(&fixture("azorius-signet")), 1);
+        assert_eq!(goldfish::production(&fixture("dark-ritual")), 3);
+        assert_eq!(goldfish::production(&fixture("rakdos-carnarium")), 2);
+    }
+
+    #[test]
+    fn simulate_lands_only() {
+        let deck = goldfish_deck(&[]);
+        let roles = Roles::new(&basics(&deck));
+        let rule = KeepRule::parse("never").unwrap();
+
+        let result = goldfish::simulate(&deck, &roles, 50, 5, Play::OnThePlay, &rule, Some(7)).unwrap();
+        assert_eq!(result.commander_turn.get(&5), Some(&50));
+        assert_eq!(result.mulligans.get(&0), Some(&50));
+        for (turn, mana) in result.mana.iter().enumerate() {
+            assert_eq!(mana.get(&(turn + 1)), Some(&50));
+        }
+    }
+
+    #[test]
+    fn simulate_is_reproducible_with_a_seed() {
+        let deck = goldfish_deck(&["sol-ring", "llanowar-elves", "noble-hierarch", "azorius-signet"]);
+        let roles = Roles::new(&basics(&deck));
+        let rule = KeepRule::parse("lands=2-5,ramp").unwrap();
+
+        let first = goldfish::simulate(&deck, &roles, 200, 6, Play::OnTheDraw, &rule, Some(42)).unwrap();
+        let second = goldfish::simulate(&deck, &roles, 200, 6, Play::OnTheDraw, &rule, Some(42)).unwrap();
+        assert_eq!(first.mana, second.mana);
+        assert_eq!(first.commander_turn, second.commander_turn);
+        assert_eq!(first.mulligans, second.mulligans);
+        // Ramp only ever speeds the commander up
+        assert!(first.commander_turn.keys().all(|turn| *turn <= 5));
+    }
+
+    #[test]
+    fn simulate_rejects_unplayable_runs() {
+        let deck = goldfish_deck(&[]);
+        let roles = Roles::new(&basics(&deck));
+        let rule = KeepRule::parse("2-5").unwrap();
+
+        assert!(matches!(goldfish::simulate(&deck, &roles, 10, 0, Play::OnThePlay, &rule, Some(1)), Err(CEerror::SimulationError(_))));
+        let small = Deck::new(String::from("small"), Vec::new(), vec![fixture("forest"); 8]);
+        assert!(matches!(goldfish::simulate(&small, &roles, 10, 3, Play::OnThePlay, &rule, Some(1)), Err(CEerror::SimulationError(_))));
+    }
+
+    #[test]
+    fn evaluate_keep_rates() {
+        let deck = goldfish_deck(&[]);
+        let roles = Roles::new(&basics(&deck));
+        let rules = vec![KeepRule::parse("lands=0-7").unwrap(), KeepRule::parse("lands=0-6,mulligans=2").unwrap()];
+
+        let result = goldfish::evaluate(&deck, &roles, &rules, 100, 2, Some(3)).unwrap();
+        assert_eq!(result[0].keep_rate, 1.0);
+        assert_eq!(result[0].kept.len(), 2);
+        // Seven forests are never kept, after two mulligans the hand is kept anyway
+        assert_eq!(result[1].keep_rate, 0.0);
+        assert_eq!(result[1].expected_mulligans, 2.0);
+        assert_eq!(result[1].mulliganed.len(), 2);
+    }
+
+    #[test]
+    fn mana_curve_counts_each_spell_once() {
+        let library = ["forest", "darksteel-citadel", "sol-ring", "llanowar-elves", "solemn-simulacrum", "titania-protector-of-argoth"]
+            .iter().map(|name| fixture(name)).collect();
+        let deck = Deck::new(String::from("curve"), Vec::new(), library);
+
+        let curve: Vec<(u8, usize)> = basic::mana_cost(&deck).iter().map(|(cmc, cards)| (*cmc, cards.len())).collect();
+        assert_eq!(curve, vec![(1, 2), (4, 1), (5, 1)]);
+    }
+
+    #[test]
+    fn powerlevel_factors() {
+        let library = ["forest", "darksteel-citadel", "sol-ring", "llanowar-elves", "solemn-simulacrum", "titania-protector-of-argoth"]
+            .iter().map(|name| fixture(name)).collect();
+        let deck = Deck::new(String::from("factors"), Vec::new(), library);
+        let basics = basics(&deck);
+
+        let names: Vec<String> = (0..10).map(|i| format!("Tutor {}", i)).collect();
+        let tutors = Tutor {
+            tutor: names.iter().map(|name| (name, Vec::new())).collect(),
+            fetches: HashMap::new(),
+            landramp: HashMap::new(),
+        };
+
+        let powerlevel = Powerlevel::new(&basics, &tutors);
+        let factor = |name: &str| powerlevel.factors.iter().find(|factor| factor.name == name).unwrap();
+
+        assert_eq!(factor("Average CMC").value, 2.75);
+        assert_eq!(factor("Average CMC").points, 0.9375);
+        // Saturated at eight tutors
+        assert_eq!(factor("Tutors").value, 10.0);
+        assert_eq!(factor("Tutors").points, 2.0);
+        assert_eq!(factor("Combos").points, 0.0);
+        assert_eq!(factor("Combo pieces").points, 0.0);
+
+        let total: f32 = 1.0 + powerlevel.factors.iter().map(|factor| factor.points).sum::<f32>();
+        assert_eq!(powerlevel.score, total.round() as u8);
+        assert!(powerlevel.factors.iter().all(|factor| factor.points <= factor.max_points));
+        assert_eq!(powerlevel.factors.iter().map(|factor| factor.max_points).sum::<f32>(), 9.0);
+    }
+
+    fn names(cards: Vec<&Card>) -> Vec<&str> {
+        cards.iter().map(|card| card.name.as_str()).collect()
+    }
+
+    #[test]
+    fn wincons() {
+        let library = ["blood-artist", "thassas-oracle", "platinum-angel", "llanowar-elves"].iter().map(|name| fixture(name)).collect();
+        let deck = Deck::new(String::from("wincon"), Vec::new(), library);
+
+        assert_eq!(names(basic::effect(&deck).wincon), vec!["Blood Artist", "Thassa's Oracle"]);
+    }
+
+    #[test]
+    fn engines() {
+        let library = ["howling-mine", "sylvan-library", "font-of-mythos", "rhystic-study", "archivist", "mind-stone", "commanders-sphere", "divination"]
+            .iter().map(|name| fixture(name)).collect();
+        let deck = Deck::new(String::from("engine"), Vec::new(), library);
+
+        assert_eq!(names(basic::effect(&deck).engine), vec!["Howling Mine", "Sylvan Library", "Font of Mythos", "Rhystic Study", "Archivist"]);
+    }
+
+    #[test]
+    fn taxes() {
+        let library = ["thalia-guardian-of-thraben", "rhystic-study", "sol-ring", "divination"].iter().map(|name| fixture(name)).collect();
+        let deck = Deck::new(String::from("tax"), Vec::new(), library);
+
+        assert_eq!(names(basic::effect(&deck).tax), vec!["Thalia, Guardian of Thraben", "Rhystic Study"]);
+    }
+
+    fn all_fixtures() -> Vec<Card> {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures");
+        let mut names: Vec<String> = fs::read_dir(dir).unwrap()
+            .map(|entry| entry.unwrap().path().file_stem().unwrap().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names.iter().map(|name| fixture(name)).collect()
+    }
+    // The checks as they were before the query rewrite
+    fn legacy_stax(card: &Card) -> bool {
+        (card.contains(Restrictions::CanT, CardFields::Restrictions)
+            || (card.contains(Keys::Player, CardFields::Restrictions) && card.contains(Restrictions::Each, CardFields::Restrictions))
+            || (card.contains(Keys::Opponent, CardFields::Keys) && card.contains(Restrictions::Each, CardFields::Restrictions))
+            || (card.contains(Keys::Cost, CardFields::Keys) && card.contains(Restrictions::More, CardFields::Restrictions))
+            || (card.contains(Restrictions::CanT, CardFields::Restrictions) && card.contains(Keys::Activate, CardFields::Keys))
+            || (card.contains(Restrictions::Non, CardFields::Restrictions) && card.contains(CardType::Basic, CardFields::OracleType))
+            || (card.contains(CardType::Land(None), CardFields::OracleType) && card.contains(Restrictions::Dont, CardFields::Restrictions) && card.contains(Restrictions::Untap, CardFields::Restrictions)))
+        && (card.contains(CardType::Creature(None), CardFields::CardType)
+            || card.contains(CardType::Artifact(None), CardFields::CardType)
+            || card.contains(CardType::Enchantment(None), CardFields::CardType)
+            || card.contains(CardType::Planeswalker, CardFields::CardType))
+        && !card.contains(Keys::Add, CardFields::Keys)
+        && !card.contains(Keys::ETB, CardFields::Keys)
+        && !card.contains("CARDNAME", CardFields::OracleTemplate)
+    }
+    fn legacy_payoff(card: &Card) -> bool {
+        (card.contains(Restrictions::Whenever, CardFields::Restrictions) && !card.contains(Keys::Tapped, CardFields::Keys)
+        && (card.contains(Keys::ETB, CardFields::Keys)
+            || (card.contains(Keys::Cast, CardFields::Keys) && card.contains(Restrictions::You, CardFields::Restrictions))
+            || (card.contains(Keys::Copy, CardFields::Keys) && card.contains(Restrictions::You, CardFields::Restrictions))
+            || (card.contains(Keys::Play, CardFields::Keys) && card.contains(Restrictions::You, CardFields::Restrictions))
+            || (card.contains(Keys::Damage, CardFields::Keys) && !card.contains(Restrictions::You, CardFields::Restrictions))
+            || card.contains(Restrictions::Die, CardFields::Restrictions)
+            || (card.contains(Keys::Discard, CardFields::Keys) && !card.contains(Restrictions::Drawstep, CardFields::Restrictions))
+            || (card.contains(Restrictions::GainLife, CardFields::Restrictions) && card.contains(Restrictions::You, CardFields::Restrictions))
+            || (card.contains(Keys::Draw, CardFields::Keys) && !card.contains(Restrictions::Drawstep, CardFields::Restrictions) && card.contains(Restrictions::You, CardFields::Restrictions))))
+        || (card.contains(Keys::Sacrifice, CardFields::Keys)
+            && card.contains(CardType::Creature(None), CardFields::OracleType)
+            && !card.contains(Keys::Search, CardFields::Keys)
+            && !card.contains("CARDNAME", CardFields::OracleTemplate))
+        || (card.contains(Keys::Sacrifice, CardFields::Keys)
+            && card.contains(CardType::Artifact(None), CardFields::OracleType)
+            && !card.contains(Keys::Search, CardFields::Keys)
+            && !card.contains("CARDNAME", CardFields::OracleTemplate))
+    }
+    fn legacy_bound<'deck>(deck: &'deck Deck, tutor: &Card, cardtype: &CardType, inclusive: bool) -> Vec<&'deck str> {
+        let mut result = Vec::new();
+
+        for restriction in tutor.restrictions.iter().flatten() {
+            let bound = match restriction.to_string().parse::<f32>() {
+                Ok(t) => t,
+                Err(_) => continue,
+            };
+            for card in &deck.library {
+                let fits = match (bound == 0.0, inclusive) {
+                    (true, true) => card.cmc == 0.0,
+                    (true, false) => false,
+                    (false, true) => card.cmc <= bound && card.contains(cardtype, CardFields::CardType),
+                    (false, false) => card.cmc < bound && card.contains(cardtype, CardFields::CardType),
+                };
+                if card.name != tutor.name && fits {
+                    result.push(card.name.as_str());
+                }
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn query_checks_match_the_legacy_checks() {
+        let cards = all_fixtures();
+
+        for card in &cards {
+            assert_eq!(basic::is_stax(card), legacy_stax(card), "stax {}", card.name);
+            assert_eq!(basic::is_payoff(card), legacy_payoff(card), "payoff {}", card.name);
+        }
+        assert!(cards.iter().any(basic::is_stax));
+        assert!(cards.iter().any(basic::is_payoff));
+    }
+
+    #[test]
+    fn query_bounds_match_the_legacy_bounds() {
+        let deck = Deck::new(String::from("bounds"), Vec::new(), all_fixtures());
+        let cardtypes = [CardType::Artifact(None), CardType::Creature(None), CardType::Enchantment(None), CardType::Instant(None), CardType::Sorcery(None), CardType::Land(None)];
+
+        for tutor in &deck.library {
+            for cardtype in &cardtypes {
+                assert_eq!(names(tutor::less(&deck, tutor, cardtype.clone())), legacy_bound(&deck, tutor, cardtype, false), "less {} {:?}", tutor.name, cardtype);
+                assert_eq!(names(tutor::less_or_equal(&deck, tutor, cardtype.clone())), legacy_bound(&deck, tutor, cardtype, true), "or less {} {:?}", tutor.name, cardtype);
+            }
+        }
+        let trinket = deck.library.iter().find(|card| card.name == "Trinket Mage").unwrap();
+        assert!(names(tutor::less_or_equal(&deck, trinket, CardType::Artifact(None))).contains(&"Sol Ring"));
+    }
+
+    #[test]
+    fn any_tutors_find_every_card() {
+        let deck = Deck::new(String::from("tutors"), Vec::new(), all_fixtures());
+        let tutors = tutor::tutor(&deck);
+
+        let found = |name: &str| {
+            let mut found = names(tutors.tutor[&name.to_string()].clone());
+            found.sort();
+            found.dedup();
+            found
+        };
+
+        for name in ["Diabolic Intent", "Razaketh, the Foulblooded", "Scrapyard Recombiner"] {
+            assert_eq!(found(name).len(), deck.library.len() - 1, "{}", name);
+            assert!(!found(name).contains(&name));
+        }
+        // Arcum sacrifices creatures too, but only finds artifacts
+        assert!(found("Arcum Dagsson").contains(&"Sol Ring"));
+        assert!(!found("Arcum Dagsson").contains(&"Llanowar Elves"));
+    }
+
+    #[test]
+    fn color_sources() {
+        let names = ["forest", "windswept-heath", "evolving-wilds", "command-tower", "birds-of-paradise", "llanowar-elves", "sol-ring", "divination"];
+        let deck = Deck::new(String::from("sources"), Vec::new(), names.iter().map(|name| fixture(name)).collect());
+        let basics = basics(&deck);
+        let produces = |name: &str, color: Colors| {
+            let card = deck.library.iter().find(|card| card.name == name).unwrap();
+            manabase::produces(card, &deck, &basics, color)
+        };
+
+        assert!(produces("Forest", Colors::Green));
+        assert!(!produces("Forest", Colors::White));
+        assert!(produces("Windswept Heath", Colors::Green));
+        assert!(produces("Windswept Heath", Colors::White));
+        assert!(!produces("Windswept Heath", Colors::Blue));
+        // Only a Forest to find
+        assert!(produces("Evolving Wilds", Colors::Green));
+        assert!(!produces("Evolving Wilds", Colors::Black));
+        assert!(produces("Command Tower", Colors::Blue));
+        assert!(produces("Birds of Paradise", Colors::Red));
+        assert!(produces("Llanowar Elves", Colors::Green));
+        assert!(!produces("Llanowar Elves", Colors::Blue));
+        assert!(!produces("Sol Ring", Colors::Green));
+        assert!(!produces("Divination", Colors::Blue));
+    }
+
+    #[test]
+    fn binomial_coefficients() {
+        assert_eq!(probability::choose(5, 2), 10.0);
+        assert_eq!(probability::choose(52, 5), 2_598_960.0);
+        assert_eq!(probability::choose(4, 0), 1.0);
+        assert_eq!(probability::choose(4, 4), 1.0);
+        assert_eq!(probability::choose(3, 5), 0.0);
+    }
+
+    #[test]
+    fn hypergeometric_values_and_edges() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+
+        assert!(close(probability::hypergeometric(60, 4, 7, 1).unwrap(), 0.3994996257446656));
+        assert!(close(probability::hypergeometric(99, 1, 7, 1).unwrap(), 7.0 / 99.0));
+        assert!(close(probability::hypergeometric(99, 10, 7, 2).unwrap(), 0.14681859748466186));
+        assert_eq!(probability::hypergeometric(99, 10, 7, 0).unwrap(), 1.0);
+        // More hits wanted than there are in the deck
+        assert_eq!(probability::hypergeometric(10, 2, 5, 3).unwrap(), 0.0);
+        // Every card drawn
+        assert!(close(probability::hypergeometric(10, 2, 10, 2).unwrap(), 1.0));
+
+        assert!(matches!(probability::hypergeometric(10, 2, 11, 1), Err(CEerror::HyperGeoFailed)));
+        assert!(matches!(probability::hypergeometric(10, 11, 5, 1), Err(CEerror::HyperGeoFailed)));
+        assert!(matches!(probability::hypergeometric(10, 2, 5, 6), Err(CEerror::HyperGeoFailed)));
+    }
+
+    #[test]
+    fn at_least_uses_the_library() {
+        let deck = goldfish_deck(&["sol-ring", "llanowar-elves"]);
+        let group: Vec<&Card> = deck.library.iter().filter(|card| card.name != "Forest").collect();
+
+        let on_the_play = probability::at_least(&deck, &group, 1, 1, Play::OnThePlay).unwrap();
+        assert!((on_the_play - probability::hypergeometric(99, 2, 7, 1).unwrap()).abs() < 1e-12);
+        let on_the_draw = probability::at_least(&deck, &group, 1, 1, Play::OnTheDraw).unwrap();
+        assert!((on_the_draw - probability::hypergeometric(99, 2, 8, 1).unwrap()).abs() < 1e-12);
+        // The commander is no card of the library
+        let commander: Vec<&Card> = deck.commander.iter().collect();
+        assert_eq!(probability::at_least(&deck, &commander, 1, 1, Play::OnThePlay).unwrap(), 0.0);
+
+        assert!(matches!(probability::at_least(&deck, &group, 1, 0, Play::OnThePlay), Err(CEerror::HyperGeoFailed)));
+        let small = Deck::new(String::from("small"), Vec::new(), vec![fixture("sol-ring"); 5]);
+        assert!(matches!(probability::at_least(&small, &[], 1, 1, Play::OnThePlay), Err(CEerror::HyperGeoFailed)));
+    }
+
+    #[test]
+    fn combo_pieces_are_library_cards() {
+        let deck = goldfish_deck(&["sol-ring", "llanowar-elves"]);
+        let combo = Combo {
+            id: String::from("1"),
+            pieces: vec![String::from("Titania, Protector of Argoth"), String::from("Sol Ring"), String::from("Llanowar Elves")],
+            color_identity: Vec::new(),
+            prerequisites: Vec::new(),
+            steps: Vec::new(),
+            results: Vec::new(),
+        };
+
+        let result = ComboResult::from(true, combo, Vec::new());
+        assert_eq!(names(probability::combo_pieces(&deck, &result)), vec!["Sol Ring", "Llanowar Elves"]);
+    }
+}
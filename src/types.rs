@@ -1,12 +1,13 @@
 #![allow(non_camel_case_types)]
 
 
-use std::{fmt::{self, Debug, Display}, error, fs::*};
+use std::{fmt::{self, Debug, Display}, error, fs::*, sync::OnceLock};
 use strum_macros::{EnumIter};
 use serde::{Serialize, Deserialize};
 
 
-use crate::logic;
+use crate::{logic, config};
+use regex::Regex;
 
 
 /************************************** Macros ***********************************************************/
@@ -40,6 +41,13 @@ pub enum CEerror {
     CardNotFound,
     ComboError,
     HyperGeoFailed,
+    ConfigError(String),
+    ScryfallError(String),
+    QueryError(String),
+    // Goldfish and mulligan runs the deck can not play, e.g. zero turns or too few cards
+    SimulationError(String),
+    // Files written for other programs, like the MTGO .dek export
+    ExportError(String),
 }
 impl_fmt!(for CEerror);
 impl error::Error for CEerror {} 
@@ -57,6 +65,8 @@ pub enum CardType{
     Enchantment(Option<Vec<EnchantmentSubtype>>),
     Land(Option<Vec<LandSubtype>>),
     Planeswalker,
+    // Draft matters cards, never legal in a deck
+    Conspiracy,
     Token,
     Basic,
     InvalidCardType, 
@@ -361,6 +371,7 @@ Zubera,
 #[derive(Debug, Clone,Eq, PartialEq, EnumIter, Serialize, Deserialize, Copy)]
 pub enum EnchantmentSubtype{
     Aura, 
+    Background, 
     Cartouche, 
     Class, 
     Curse, 
@@ -809,6 +820,20 @@ pub enum Colors {
 }
 impl_fmt!(for Zones, Keywords);
 impl Colors {
+    // The five colors a color identity is made of, in WUBRG order
+    pub fn identity() -> [Colors; 5] {
+        [Colors::White, Colors::Blue, Colors::Black, Colors::Red, Colors::Green]
+    }
+    pub fn from_symbol(symbol: char) -> Option<Colors> {
+        match symbol.to_ascii_uppercase() {
+            'W' => Some(Colors::White),
+            'U' => Some(Colors::Blue),
+            'B' => Some(Colors::Black),
+            'R' => Some(Colors::Red),
+            'G' => Some(Colors::Green),
+            _ => None,
+        }
+    }
     pub fn to_key(&self) -> Keys {
         match self {
             Colors::White => Keys::White,
@@ -845,7 +870,398 @@ impl fmt::Display for Colors {
         )
     }
 }
+/*************************************** Mana Cost *****************************************************************/
+
+// One symbol of a mana cost, {2/W} is TwoHybrid and {G/U/P} is HybridPhyrexian
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManaSymbol {
+    Generic(u8),
+    Colored(Colors),
+    Colorless,
+    Hybrid(Colors, Colors),
+    TwoHybrid(Colors),
+    Phyrexian(Colors),
+    HybridPhyrexian(Colors, Colors),
+    Variable(char),
+    Snow,
+    Other(String),
+}
+
+impl ManaSymbol {
+    pub fn parse(symbol: &str) -> ManaSymbol {
+        let parts: Vec<&str> = symbol.split('/').collect();
+        let color = |part: &str| if part.len() == 1 { part.chars().next().and_then(Colors::from_symbol) } else { None };
+
+        match parts.as_slice() {
+            [t] if t.parse::<u8>().is_ok() => ManaSymbol::Generic(t.parse().unwrap_or(0)),
+            ["C"] => ManaSymbol::Colorless,
+            ["S"] => ManaSymbol::Snow,
+            [t] if matches!(*t, "X" | "Y" | "Z") => ManaSymbol::Variable(t.chars().next().unwrap_or('X')),
+            [t] if color(t).is_some() => ManaSymbol::Colored(color(t).unwrap_or(Colors::Colourless)),
+            ["2", t] if color(t).is_some() => ManaSymbol::TwoHybrid(color(t).unwrap_or(Colors::Colourless)),
+            [t, "P"] if color(t).is_some() => ManaSymbol::Phyrexian(color(t).unwrap_or(Colors::Colourless)),
+            [a, b] if color(a).is_some() && color(b).is_some() => ManaSymbol::Hybrid(color(a).unwrap_or(Colors::Colourless), color(b).unwrap_or(Colors::Colourless)),
+            [a, b, "P"] if color(a).is_some() && color(b).is_some() => ManaSymbol::HybridPhyrexian(color(a).unwrap_or(Colors::Colourless), color(b).unwrap_or(Colors::Colourless)),
+            _ => ManaSymbol::Other(symbol.to_string()),
+        }
+    }
+    // X is zero everywhere but on the stack, {2/W} counts with its generic half
+    pub fn mana_value(&self) -> f32 {
+        match self {
+            ManaSymbol::Generic(t) => *t as f32,
+            ManaSymbol::TwoHybrid(_) => 2.0,
+            ManaSymbol::Variable(_) => 0.0,
+            ManaSymbol::Other(t) if t.starts_with('H') => 0.5,
+            _ => 1.0,
+        }
+    }
+    // Colors that can pay this symbol, every side of a hybrid counts
+    pub fn colors(&self) -> Vec<Colors> {
+        match self {
+            ManaSymbol::Colored(t) | ManaSymbol::TwoHybrid(t) | ManaSymbol::Phyrexian(t) => vec![*t],
+            ManaSymbol::Hybrid(a, b) | ManaSymbol::HybridPhyrexian(a, b) => vec![*a, *b],
+            ManaSymbol::Colorless => vec![Colors::Colourless],
+            _ => Vec::new(),
+        }
+    }
+}
+impl fmt::Display for ManaSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let letter = |color: &Colors| color.to_string().trim_matches(|c| c == '{' || c == '}').to_string();
+
+        match self {
+            ManaSymbol::Generic(t) => write!(f, "{{{}}}", t),
+            ManaSymbol::Colored(t) => write!(f, "{}", t),
+            ManaSymbol::Colorless => write!(f, "{{C}}"),
+            ManaSymbol::Hybrid(a, b) => write!(f, "{{{}/{}}}", letter(a), letter(b)),
+            ManaSymbol::TwoHybrid(t) => write!(f, "{{2/{}}}", letter(t)),
+            ManaSymbol::Phyrexian(t) => write!(f, "{{{}/P}}", letter(t)),
+            ManaSymbol::HybridPhyrexian(a, b) => write!(f, "{{{}/{}/P}}", letter(a), letter(b)),
+            ManaSymbol::Variable(t) => write!(f, "{{{}}}", t),
+            ManaSymbol::Snow => write!(f, "{{S}}"),
+            ManaSymbol::Other(t) => write!(f, "{{{}}}", t),
+        }
+    }
+}
+
+// Stored and serialized as the scryfall string, e.g. "{2}{G/W}{G/W}"
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub struct ManaCost {
+    pub symbols: Vec<ManaSymbol>,
+}
+
+impl ManaCost {
+    pub fn parse(input: &str) -> ManaCost {
+        let symbols = input.split('{').skip(1)
+            .map(|symbol| ManaSymbol::parse(symbol.split('}').next().unwrap_or("")))
+            .collect();
+
+        ManaCost { symbols }
+    }
+    pub fn mana_value(&self) -> f32 {
+        self.symbols.iter().map(ManaSymbol::mana_value).sum()
+    }
+    // Symbols a color can pay for, hybrid symbols count for each of their colors
+    pub fn pips(&self, color: Colors) -> u8 {
+        self.symbols.iter().filter(|symbol| symbol.colors().contains(&color)).count() as u8
+    }
+    // Symbols only this color can pay, hybrid, {2/W} and Phyrexian symbols can be paid otherwise
+    pub fn required_pips(&self, color: Colors) -> u8 {
+        self.symbols.iter().filter(|symbol| match symbol {
+            ManaSymbol::Colored(t) => *t == color,
+            ManaSymbol::Colorless => color == Colors::Colourless,
+            _ => false,
+        }).count() as u8
+    }
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+    pub fn contains(&self, search: &str) -> bool {
+        self.to_string().contains(search)
+    }
+}
+impl fmt::Display for ManaCost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for symbol in &self.symbols {
+            write!(f, "{}", symbol)?;
+        }
+        Ok(())
+    }
+}
+impl From<String> for ManaCost {
+    fn from(input: String) -> ManaCost {
+        ManaCost::parse(&input)
+    }
+}
+impl From<ManaCost> for String {
+    fn from(cost: ManaCost) -> String {
+        cost.to_string()
+    }
+}
+
+
+/************************************** Legality ********************************************************/
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Violation {
+    MissingCommander,
+    IllegalCommander(String),
+    InvalidPair(String, String),
+    TooManyCommanders(usize),
+    DeckSize { expected: usize, found: usize },
+    Singleton { card: String, copies: usize },
+    ColorIdentity(String),
+    Banned(String),
+    Companion { card: String, reason: String },
+}
+impl_fmt!(for Violation);
+
+mod legality {
+    use super::*;
+
+    // Commander banned list as of the September 2024 update
+    const BANNED: [&str; 63] = [
+        "Ancestral Recall", "Balance", "Biorhythm", "Black Lotus", "Channel", "Chaos Orb",
+        "Coalition Victory", "Dockside Extortionist", "Emrakul, the Aeons Torn",
+        "Falling Star", "Fastbond", "Flash", "Gifts Ungiven", "Griselbrand", "Hullbreacher",
+        "Iona, Shield of Emeria", "Jeweled Lotus", "Karakas", "Leovold, Emissary of Trest", "Library of Alexandria",
+        "Limited Resources", "Lutri, the Spellchaser", "Mana Crypt", "Mox Emerald", "Mox Jet", "Mox Pearl", "Mox Ruby",
+        "Mox Sapphire", "Nadu, Winged Wisdom", "Panoptic Mirror", "Paradox Engine", "Primeval Titan", "Prophet of Kruphix",
+        "Recurring Nightmare", "Shahrazad", "Sundering Titan", "Sway of the Stars",
+        "Sylvan Primordial", "Time Vault", "Time Walk", "Tinker", "Tolarian Academy", "Trade Secrets", "Upheaval",
+        "Worldfire", "Yawgmoth's Bargain", "Contract from Below", "Darkpact", "Golos, Tireless Pilgrim",
+        // Ante cards and cards banned for offensive content
+        "Amulet of Quoz", "Bronze Tablet", "Demonic Attorney", "Jeweled Bird", "Rebirth", "Tempest Efreet", "Timmerian Fiends",
+        "Cleanse", "Crusade", "Imprison", "Invoke Prejudice", "Jihad", "Pradesh Gypsies", "Stone-Throwing Devils",
+    ];
+    // Legal in the library, but not in the command zone
+    const BANNED_AS_COMMANDER: [&str; 1] = ["Rofellos, Llanowar Emissary"];
+
+    pub fn commanders(deck: &Deck) -> Vec<Violation> {
+        let mut violations = Vec::<Violation>::new();
+
+        match deck.commander.len() {
+            0 => violations.push(Violation::MissingCommander),
+            1 => {
+                if !can_be_commander(&deck.commander[0]) {
+                    violations.push(Violation::IllegalCommander(deck.commander[0].name.to_string()));
+                }
+            },
+            2 => {
+                let first = &deck.commander[0];
+                let second = &deck.commander[1];
+                // A background is no creature, but is allowed next to a commander choosing one
+                if background(first) && chooses_background(second) {
+                    if !can_be_commander(second) {
+                        violations.push(Violation::IllegalCommander(second.name.to_string()));
+                    }
+                } else if background(second) && chooses_background(first) {
+                    if !can_be_commander(first) {
+                        violations.push(Violation::IllegalCommander(first.name.to_string()));
+                    }
+                } else {
+                    for commander in &deck.commander {
+                        if !can_be_commander(commander) {
+                            violations.push(Violation::IllegalCommander(commander.name.to_string()));
+                        }
+                    }
+                    if !partners(first, second) {
+                        violations.push(Violation::InvalidPair(first.name.to_string(), second.name.to_string()));
+                    }
+                }
+            },
+            t => violations.push(Violation::TooManyCommanders(t)),
+        }
+        violations
+    }
+    pub fn size(deck: &Deck) -> Vec<Violation> {
+        let found = deck.commander.len() + deck.library.len();
+
+        if found != 100 {
+            return vec![Violation::DeckSize { expected: 100, found }];
+        }
+        Vec::new()
+    }
+    pub fn singleton(deck: &Deck) -> Vec<Violation> {
+        let mut violations = Vec::<Violation>::new();
+        let mut copies = Vec::<(&Card, usize)>::new();
+
+        for card in deck.library.iter().chain(deck.commander.iter()) {
+            match copies.iter_mut().find(|(known, _)| known.name == card.name) {
+                Some((_, num)) => *num += 1,
+                None => copies.push((card, 1)),
+            }
+        }
+
+        for (card, num) in copies {
+            if num > allowed_copies(card) {
+                violations.push(Violation::Singleton { card: card.name.to_string(), copies: num });
+            }
+        }
+        violations
+    }
+    pub fn color_identity(deck: &Deck) -> Vec<Violation> {
+        let mut violations = Vec::<Violation>::new();
+        let identity = deck.color_identity();
+
+        for card in deck.library.iter().chain(deck.companion.iter()) {
+            if card.color_identity.iter().any(|color| !identity.contains(color))
+            && !violations.contains(&Violation::ColorIdentity(card.name.to_string())) {
+                violations.push(Violation::ColorIdentity(card.name.to_string()));
+            }
+        }
+        violations
+    }
+    // Conspiracies count as banned, they only exist for draft
+    pub fn banned(deck: &Deck) -> Vec<Violation> {
+        let mut violations = Vec::<Violation>::new();
+
+        for card in deck.commander.iter().chain(deck.library.iter()).chain(deck.companion.iter()) {
+            if (BANNED.contains(&card.name.as_str()) || card.cardtype.contains(&CardType::Conspiracy))
+            && !violations.contains(&Violation::Banned(card.name.to_string())) {
+                violations.push(Violation::Banned(card.name.to_string()));
+            }
+        }
+        for card in &deck.commander {
+            if BANNED_AS_COMMANDER.contains(&card.name.as_str()) && !violations.contains(&Violation::Banned(card.name.to_string())) {
+                violations.push(Violation::Banned(card.name.to_string()));
+            }
+        }
+        violations
+    }
+    // Companion restrictions apply to the starting deck, which includes the commander
+    pub fn companion(deck: &Deck) -> Vec<Violation> {
+        let mut violations = Vec::<Violation>::new();
+        let starting: Vec<&Card> = deck.commander.iter().chain(deck.library.iter()).collect();
+        let nonland: Vec<&&Card> = starting.iter().filter(|card| !card.contains(CardType::Land(None), CardFields::CardType)).collect();
+
+        for companion in &deck.companion {
+            let reason = match companion.name.as_str() {
+                "Gyruda, Doom of Depths" => nonland.iter().find(|card| !(card.cmc as u8).is_multiple_of(2))
+                    .map(|card| format!("{} has an odd mana value", card.name)),
+                "Obosh, the Preypiercer" => nonland.iter().find(|card| (card.cmc as u8).is_multiple_of(2))
+                    .map(|card| format!("{} has an even mana value", card.name)),
+                "Keruga, the Macrosage" => nonland.iter().find(|card| card.cmc < 3.0)
+                    .map(|card| format!("{} has a mana value less than 3", card.name)),
+                "Lurrus of the Dream-Den" => starting.iter().find(|card| permanent(card) && card.cmc > 2.0)
+                    .map(|card| format!("{} is a permanent with mana value greater than 2", card.name)),
+                "Jegantha, the Wellspring" => starting.iter().find(|card| repeated_symbol(card))
+                    .map(|card| format!("{} has a repeated mana symbol", card.name)),
+                "Kaheera, the Orphanguard" => starting.iter().find(|card| !kaheera(card))
+                    .map(|card| format!("{} is not a Cat, Elemental, Nightmare, Dinosaur or Beast", card.name)),
+                "Zirda, the Dawnwaker" => starting.iter().find(|card| permanent(card) && !card.oracle_text.contains(':'))
+                    .map(|card| format!("{} is a permanent without activated ability", card.name)),
+                "Umori, the Collector" => umori(&nonland),
+                "Yorion, Sky Nomad" => Some(String::from("Starting deck needs at least 120 cards")),
+                _ => {
+                    if companion.contains(Keywords::Companion, CardFields::Keywords) {
+                        None
+                    } else {
+                        Some(String::from("Card has no companion ability"))
+                    }
+                },
+            };
+
+            if let Some(reason) = reason {
+                violations.push(Violation::Companion { card: companion.name.to_string(), reason });
+            }
+        }
+        violations
+    }
+    fn can_be_commander(card: &Card) -> bool {
+        (card.legendary && card.contains(CardType::Creature(None), CardFields::CardType))
+        || card.contains("can be your commander", CardFields::OraclePhrase)
+    }
+    fn partners(first: &Card, second: &Card) -> bool {
+        if first.contains("Partner with", CardFields::OraclePhrase) || second.contains("Partner with", CardFields::OraclePhrase) {
+            return first.contains(&second.name, CardFields::OraclePhrase) && second.contains(&first.name, CardFields::OraclePhrase);
+        }
+        (first.contains(Keywords::Partner, CardFields::Keywords) && second.contains(Keywords::Partner, CardFields::Keywords))
+        || (first.contains("Friends forever", CardFields::OraclePhrase) && second.contains("Friends forever", CardFields::OraclePhrase))
+    }
+    fn background(card: &Card) -> bool {
+        card.cardtype.contains(&CardType::Enchantment(Some(vec![EnchantmentSubtype::Background])))
+    }
+    fn chooses_background(card: &Card) -> bool {
+        card.contains("Choose a Background", CardFields::OraclePhrase)
+    }
+    fn allowed_copies(card: &Card) -> usize {
+        if card.contains(CardType::Basic, CardFields::CardType) 
+        || card.contains(Restrictions::AnyNumber, CardFields::Restrictions) {
+            return usize::MAX;
+        }
+        // Seven Dwarves and Nazgul limit themselves
+        if card.contains("deck can have up to seven", CardFields::OraclePhrase) {
+            return 7;
+        }
+        if card.contains("deck can have up to nine", CardFields::OraclePhrase) {
+            return 9;
+        }
+        1
+    }
+    fn permanent(card: &Card) -> bool {
+        !(card.contains(CardType::Instant(None), CardFields::CardType) || card.contains(CardType::Sorcery(None), CardFields::CardType))
+    }
+    fn repeated_symbol(card: &Card) -> bool {
+        let symbols = &card.mana_cost.symbols;
+        for (i, symbol) in symbols.iter().enumerate() {
+            if symbols[i + 1..].contains(symbol) {
+                return true;
+            }
+        }
+        false
+    }
+    fn kaheera(card: &Card) -> bool {
+        for cardtype in &card.cardtype {
+            if let CardType::Creature(subtypes) = cardtype {
+                return match subtypes {
+                    Some(subtypes) => subtypes.iter().any(|subtype| matches!(subtype, 
+                        CreatureSubtype::Cat | CreatureSubtype::Elemental | CreatureSubtype::Nightmare | CreatureSubtype::Dinosaur | CreatureSubtype::Beast)),
+                    None => false,
+                };
+            }
+        }
+        true
+    }
+    fn umori(nonland: &[&&Card]) -> Option<String> {
+        let first = nonland.first()?;
+
+        for cardtype in &first.cardtype {
+            if nonland.iter().all(|card| card.contains(cardtype, CardFields::CardType)) {
+                return None;
+            }
+        }
+        Some(String::from("Nonland cards do not share a card type"))
+    }
+}
 
+/*************************************** Abilities *****************************************************************/
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum AbilityKind {
+    // cost: effect
+    Activated,
+    // When, whenever and at
+    Triggered,
+    // instead, would and enters tapped
+    Replacement,
+    Static,
+    // Rules text of instants and sorceries
+    Spell,
+}
+
+// One paragraph of the oracle text with the flags found in it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ability {
+    pub kind: AbilityKind,
+    pub cost: Option<String>,
+    pub text: String,
+    pub keys: Vec<Keys>,
+    pub zones: Vec<Zones>,
+    pub keywords: Vec<Keywords>,
+    pub restrictions: Vec<Restrictions>,
+}
 
 /************************************** Card and Deck ***************************************************/
 
@@ -854,7 +1270,7 @@ impl fmt::Display for Colors {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Card {
     pub cmc: f32,
-    pub mana_cost: String,
+    pub mana_cost: ManaCost,
     pub name: String,
     pub cardtype: Vec<CardType>,
     pub legendary: bool,
@@ -867,6 +1283,28 @@ pub struct Card {
     pub keywords: Option<Vec<Keywords>>,
     pub oracle_types: Option<Vec<CardType>>,
     pub restrictions: Option<Vec<Restrictions>>,
+    // Colors of the card itself, what "search for a white card" looks at
+    #[serde(default)]
+    pub colors: Vec<Colors>,
+    // Colors for deck legality, including mana symbols in the rules text
+    #[serde(default)]
+    pub color_identity: Vec<Colors>,
+    #[serde(default)]
+    pub abilities: Vec<Ability>,
+    #[serde(skip)]
+    pub words: OracleWords,
+}
+// Lowercase tokens of the oracle text, split on first use so each card is tokenized once
+#[derive(Debug, Clone, Default)]
+pub struct OracleWords {
+    text: OnceLock<Vec<String>>,
+    template: OnceLock<Vec<String>>,
+}
+// A cache, two cards are the same no matter what was searched on them
+impl PartialEq for OracleWords {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
 }
 #[derive(Debug, Clone, Eq, PartialEq, EnumIter, Hash)]
 pub enum CardFields {
@@ -878,7 +1316,16 @@ pub enum CardFields {
     Stats,
     Commander,
     Backside,
+    // Case insensitive substring of the oracle text
     OracleText,
+    // Whole words in order, "Land" does not find "Island"
+    OracleWord,
+    // Whole words in order, plurals and verb forms count, "Aura" finds "Auras"
+    OraclePhrase,
+    // Case insensitive regular expression
+    OracleRegex,
+    // Phrase with the card's own name replaced by CARDNAME
+    OracleTemplate,
     Keys,
     Zones,
     Keywords,
@@ -890,7 +1337,7 @@ impl Card {
     pub fn new() -> Self {
         Card {
             cmc: 0.0,
-            mana_cost: String::from(""),
+            mana_cost: ManaCost::default(),
             name: String::from(""),
             cardtype: vec![CardType::InvalidCardType],
             legendary: false,
@@ -903,8 +1350,15 @@ impl Card {
             keywords: None,
             oracle_types: None,
             restrictions: None,
+            colors: Vec::new(),
+            color_identity: Vec::new(),
+            abilities: Vec::new(),
+            words: OracleWords::default(),
          }
      }
+    fn oracle_words(&self) -> &[String] {
+        self.words.text.get_or_init(|| logic::oracle::words(&logic::oracle::unescape(&self.oracle_text)))
+    }
     pub fn make(card: &String, commander: bool) -> CEResult<Self> {
         use serde_json::Value;
         use crate::logic::card_build;
@@ -992,6 +1446,19 @@ impl Card {
                 }
                 return false;
             },
+            CardFields::OracleWord => logic::oracle::matches_words(self.oracle_words(), &search.to_string(), false),
+            CardFields::OraclePhrase => logic::oracle::matches_words(self.oracle_words(), &search.to_string(), true),
+            CardFields::OracleRegex => {
+                // Invalid patterns match nothing. Compiled on every call, Query::regex compiles once for many cards
+                match Regex::new(&format!("(?i){}", search)) {
+                    Ok(regex) => regex.is_match(&logic::oracle::unescape(&self.oracle_text)),
+                    Err(_) => false,
+                }
+            },
+            CardFields::OracleTemplate => {
+                let words = self.words.template.get_or_init(|| logic::oracle::words(&logic::oracle::templated(&self.oracle_text, &self.name)));
+                logic::oracle::matches_words(words, &search.to_string(), true)
+            },
             CardFields::Keys=> {
                 match &self.keys {
                     Some(keys)=> {
@@ -1070,6 +1537,11 @@ pub struct Deck{
     pub name: String,
     pub commander: Vec<Card>,
     pub library: Vec<Card>,
+    #[serde(default)]
+    pub companion: Vec<Card>,
+    // Filled once by Deck::check, not saved
+    #[serde(skip)]
+    pub violations: Vec<Violation>,
 }
 impl Deck {
     pub fn check(mut deck: Deck, verbose: bool, offline: bool) -> CEResult<Deck>{
@@ -1105,7 +1577,7 @@ impl Deck {
                     match database::load() {
                         Ok(t) => {
                             for commander in input {
-                            commander_new.push(Card::make(&database::get(&commander, &t)?.to_string(), true)?);
+                            commander_new.push(Card::make(&database::get(&commander, t)?.to_string(), true)?);
                             }
                         },
                         Err(_) => {
@@ -1123,25 +1595,45 @@ impl Deck {
 
                 println_verbose!(verbose, "Library: {}, Commander: {:?}", deck.library.len(), deck.commander);
 
-                if deck.commander.len() + deck.library.len() == 100 {
-                    println_verbose!(verbose, "Deck complete, save deck");
-                    Deck::save(&deck);
-                    return Ok(deck);
-                } else {
-                    panic!("Deck size not 100, deck corrupted");
-                }
+                println_verbose!(verbose, "Deck complete, save deck");
+                Deck::print_violations(&mut deck);
+                Deck::save(&deck);
+                return Ok(deck);
 
             } else {
                 println_verbose!(verbose, "Deckcheck completed: Ok");
+                Deck::print_violations(&mut deck);
                 Deck::save(&deck);
                 Ok(deck)
             }
         } else {
             println_verbose!(verbose, "Deckcheck completed: Ok");
+            Deck::print_violations(&mut deck);
             Deck::save(&deck);
             Ok(deck)
         }
     }
+    fn print_violations(deck: &mut Deck) {
+        deck.violations = deck.validate();
+        for violation in &deck.violations {
+            println!("{} is not commander legal: {}", deck.name, violation);
+        }
+    }
+    // Collects every rule the deck breaks instead of stopping at the first one
+    pub fn validate(&self) -> Vec<Violation> {
+        use legality::*;
+
+        let mut violations = Vec::<Violation>::new();
+
+        violations.append(&mut commanders(self));
+        violations.append(&mut size(self));
+        violations.append(&mut singleton(self));
+        violations.append(&mut color_identity(self));
+        violations.append(&mut banned(self));
+        violations.append(&mut companion(self));
+
+        violations
+    }
     pub fn make(input: String)-> CEResult<Deck>{
        use logic::thread_fn::deck; 
        match deck(input) {
@@ -1151,13 +1643,25 @@ impl Deck {
     }  
     pub fn load(identifier: &String, verbose: bool) -> CEResult<Deck> {
 
-        let save = String::from("save/");
-        let path = format!("{}{}", save, identifier);
+        let path = config::get().saves().join(config::deck_name(identifier));
       
         match File::open(path) {
             Ok(t) => {
-                let deck = serde_json::from_reader(t).expect("Saved deck no proper json");
+                let mut deck: Deck = serde_json::from_reader(t).expect("Saved deck no proper json");
                 println_verbose!(verbose, "Deck successfully opened"); 
+
+                // Decks saved before colors, color identities and abilities were stored get them rebuilt from the card text
+                for card in deck.library.iter_mut().chain(deck.commander.iter_mut()).chain(deck.companion.iter_mut()) {
+                    if card.colors.is_empty() {
+                        card.colors = logic::card_build::colors(card, &[]);
+                    }
+                    if card.color_identity.is_empty() {
+                        card.color_identity = logic::card_build::color_identity(card, &[]);
+                    }
+                    if card.abilities.is_empty() {
+                        logic::card_build::reparse(card);
+                    }
+                }
                
                 Ok(deck) 
             },
@@ -1165,15 +1669,160 @@ impl Deck {
         }
     }
     pub fn save(deck: &Deck){
-        let save = String::from("save/");
-        serde_json::to_writer(&File::create(format!("{}{}",save, deck.name)).expect("Folder save/ not found"),
-        &deck).expect("Can not write Deck"); 
+        let path = config::get().saves().join(&deck.name);
+
+        match File::create(&path) {
+            Ok(t) => {
+                if let Err(e) = serde_json::to_writer(t, &deck) {
+                    println!("Can not write deck {}: {}", deck.name, e);
+                }
+            },
+            Err(e) => println!("Can not save deck to {}: {}", path.display(), e),
+        }
+    }
+    // Union of the commanders color identities, in WUBRG order
+    pub fn color_identity(&self) -> Vec<Colors> {
+        let mut identity = Vec::<Colors>::new();
+
+        for color in Colors::identity() {
+            if self.commander.iter().any(|commander| commander.color_identity.contains(&color)) {
+                identity.push(color);
+            }
+        }
+        identity
+    }
+    pub fn export_dek(&self, path: &str) -> CEResult<()> {
+        use crate::import::mtgo;
+
+        match std::fs::write(path, mtgo::to_dek(self)) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(CEerror::ExportError(format!("Can not write {}: {}", path, e))),
+        }
     }
     pub fn new(name: String, commander: Vec<Card>, library: Vec<Card>) -> Deck {
         Deck{
             name: name,
             commander: commander,
             library: library,
+            companion: Vec::new(),
+            violations: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use super::*;
+
+    fn fixture(name: &str, commander: bool) -> Card {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(format!("{}.json", name));
+        Card::make(&std::fs::read_to_string(path).unwrap(), commander).unwrap()
+    }
+    // Commanders and cards from the fixtures, filled up to 100 cards with Forests
+    fn deck(commanders: &[&str], cards: &[&str]) -> Deck {
+        let commander: Vec<Card> = commanders.iter().map(|name| fixture(name, true)).collect();
+        let mut library: Vec<Card> = cards.iter().map(|name| fixture(name, false)).collect();
+        while commander.len() + library.len() < 100 {
+            library.push(fixture("forest", false));
+        }
+        Deck::new(String::from("legality"), commander, library)
+    }
+
+    #[test]
+    fn mana_symbols() {
+        assert_eq!(ManaSymbol::parse("2/W"), ManaSymbol::TwoHybrid(Colors::White));
+        assert_eq!(ManaSymbol::parse("G/P"), ManaSymbol::Phyrexian(Colors::Green));
+        assert_eq!(ManaSymbol::parse("G/U/P"), ManaSymbol::HybridPhyrexian(Colors::Green, Colors::Blue));
+        assert_eq!(ManaSymbol::parse("S"), ManaSymbol::Snow);
+        assert_eq!(ManaSymbol::parse("HW"), ManaSymbol::Other(String::from("HW")));
+
+        assert_eq!(ManaSymbol::parse("2/W").mana_value(), 2.0);
+        assert_eq!(ManaSymbol::parse("G/P").mana_value(), 1.0);
+        assert_eq!(ManaSymbol::parse("S").mana_value(), 1.0);
+        assert_eq!(ManaSymbol::parse("HW").mana_value(), 0.5);
+        assert_eq!(ManaSymbol::parse("2/W").colors(), vec![Colors::White]);
+        assert!(ManaSymbol::parse("S").colors().is_empty());
+    }
+
+    #[test]
+    fn mana_costs() {
+        let cost = ManaCost::parse("{X}{X}{G}");
+        assert_eq!(cost.symbols, vec![ManaSymbol::Variable('X'), ManaSymbol::Variable('X'), ManaSymbol::Colored(Colors::Green)]);
+        assert_eq!(cost.mana_value(), 1.0);
+
+        for input in ["{2/W}{2/W}", "{G/P}", "{X}{X}{G}", "{S}{S}", "{HW}", "{3}{G/W}{C}"] {
+            assert_eq!(ManaCost::parse(input).to_string(), input);
         }
+        assert_eq!(ManaCost::parse("{2/W}{2/W}").mana_value(), 4.0);
+        assert_eq!(ManaCost::parse("{1}{HW}").mana_value(), 1.5);
+    }
+
+    #[test]
+    fn required_pips() {
+        let cost = ManaCost::parse("{1}{G}{G}{G/W}{2/U}{B/P}{C}");
+
+        assert_eq!(cost.pips(Colors::Green), 3);
+        assert_eq!(cost.pips(Colors::White), 1);
+        assert_eq!(cost.required_pips(Colors::Green), 2);
+        assert_eq!(cost.required_pips(Colors::White), 0);
+        assert_eq!(cost.required_pips(Colors::Blue), 0);
+        assert_eq!(cost.required_pips(Colors::Black), 0);
+        assert_eq!(cost.required_pips(Colors::Colourless), 1);
+    }
+
+    #[test]
+    fn legal_deck() {
+        assert!(deck(&["titania-protector-of-argoth"], &["sol-ring", "llanowar-elves"]).validate().is_empty());
+    }
+
+    #[test]
+    fn singleton() {
+        let violations = deck(&["titania-protector-of-argoth"], &["sol-ring", "sol-ring"]).validate();
+        assert_eq!(violations, vec![Violation::Singleton { card: String::from("Sol Ring"), copies: 2 }]);
+    }
+
+    #[test]
+    fn color_identity() {
+        let violations = deck(&["wilson-refined-grizzly"], &["rhystic-study", "llanowar-elves"]).validate();
+        assert_eq!(violations, vec![Violation::ColorIdentity(String::from("Rhystic Study"))]);
+
+        // Partners share their identity
+        assert!(deck(&["thrasios-triton-hero", "tymna-the-weaver"], &["rhystic-study", "blood-artist"]).validate().is_empty());
+    }
+
+    #[test]
+    fn partners_and_backgrounds() {
+        assert!(deck(&["wilson-refined-grizzly", "agent-of-the-iron-throne"], &[]).validate().is_empty());
+        assert!(deck(&["agent-of-the-iron-throne", "wilson-refined-grizzly"], &[]).validate().is_empty());
+        assert_eq!(deck(&["thrasios-triton-hero", "wilson-refined-grizzly"], &[]).validate(),
+            vec![Violation::InvalidPair(String::from("Thrasios, Triton Hero"), String::from("Wilson, Refined Grizzly"))]);
+        assert_eq!(deck(&["tymna-the-weaver", "agent-of-the-iron-throne"], &[]).validate(), vec![
+            Violation::IllegalCommander(String::from("Agent of the Iron Throne")),
+            Violation::InvalidPair(String::from("Tymna the Weaver"), String::from("Agent of the Iron Throne")),
+        ]);
+        assert_eq!(deck(&["agent-of-the-iron-throne"], &[]).validate(), vec![Violation::IllegalCommander(String::from("Agent of the Iron Throne"))]);
+    }
+
+    #[test]
+    fn companion() {
+        let mut legal = deck(&["ayli-eternal-pilgrim"], &["sol-ring"]);
+        legal.companion.push(fixture("lurrus-of-the-dream-den", false));
+        assert!(legal.validate().is_empty());
+
+        let mut illegal = deck(&["ayli-eternal-pilgrim"], &["solemn-simulacrum"]);
+        illegal.companion.push(fixture("lurrus-of-the-dream-den", false));
+        assert!(matches!(&illegal.validate()[..], [Violation::Companion { card, .. }] if card == "Lurrus of the Dream-Den"));
+
+        let mut no_companion = deck(&["titania-protector-of-argoth"], &[]);
+        no_companion.companion.push(fixture("llanowar-elves", false));
+        assert!(matches!(&no_companion.validate()[..], [Violation::Companion { .. }]));
+    }
+
+    #[test]
+    fn banned_cards() {
+        assert_eq!(deck(&["rofellos-llanowar-emissary"], &[]).validate(), vec![Violation::Banned(String::from("Rofellos, Llanowar Emissary"))]);
+        assert!(deck(&["titania-protector-of-argoth"], &["rofellos-llanowar-emissary"]).validate().is_empty());
+        assert_eq!(deck(&["titania-protector-of-argoth"], &["backup-plan"]).validate(), vec![Violation::Banned(String::from("Backup Plan"))]);
     }
 }